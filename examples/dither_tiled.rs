@@ -0,0 +1,72 @@
+// Times `Canvas::dither_bitmap` against `Canvas::dither_bitmap_tiled`:
+//
+//     cargo run --release --example dither_tiled [runs]
+
+use std::{env, time::Instant};
+
+use libcaca::{
+    dither::{DitherAlgorithm, PixelFormat, Tiling},
+    prelude::*,
+    Boundaries, Canvas, Dither, Rectangle,
+};
+
+const WIDTH: usize = 3840;
+const HEIGHT: usize = 2160;
+
+fn main() -> Result<()> {
+    let runs = env::args()
+        .nth(1)
+        .and_then(|runs| runs.parse().ok())
+        .unwrap_or(5u32);
+
+    let mut image = Vec::with_capacity(WIDTH * HEIGHT * 3);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            image.extend_from_slice(&[
+                (x * 255 / WIDTH) as u8,
+                (y * 255 / HEIGHT) as u8,
+                ((x ^ y) & 0xff) as u8,
+            ]);
+        }
+    }
+
+    let dither = Dither::builder(PixelFormat::Rgb24, WIDTH, HEIGHT).build()?;
+    let size = Boundaries {
+        width: 480,
+        height: 135,
+    };
+    let rect = Rectangle {
+        x: 0,
+        y: 0,
+        width: size.width,
+        height: size.height,
+    };
+    let mut canvas = Canvas::new(&size)?;
+    let tiling = Tiling::new();
+
+    for &algorithm in &[DitherAlgorithm::Fstein, DitherAlgorithm::Ordered8] {
+        dither.set_algorithm(algorithm)?;
+
+        let start = Instant::now();
+        for _ in 0..runs {
+            canvas.dither_bitmap(&rect, &dither, &image)?;
+        }
+        let single = start.elapsed() / runs;
+
+        let start = Instant::now();
+        for _ in 0..runs {
+            canvas.dither_bitmap_tiled(&rect, &dither, &image, &tiling)?;
+        }
+        let tiled = start.elapsed() / runs;
+
+        println!(
+            "{:>8}: single {:>10.2?}  tiled {:>10.2?}  x{:.2}",
+            algorithm.to_string(),
+            single,
+            tiled,
+            single.as_secs_f64() / tiled.as_secs_f64()
+        );
+    }
+
+    Ok(())
+}
@@ -5,8 +5,9 @@ use libcaca_sys::{
     caca_attr_to_rgb12_bg, caca_attr_to_rgb12_fg,
 };
 
-use crate::{error::Error, result::Result, Color, Style};
+use crate::{color::Rgba, error::Error, result::Result, Color, Style};
 
+#[repr(transparent)]
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub struct Attr(u32);
 
@@ -22,7 +23,56 @@ impl Into<u32> for Attr {
     }
 }
 
+impl From<Style> for Attr {
+    fn from(style: Style) -> Self {
+        Attr(style.bits())
+    }
+}
+
+// Same layout libcaca uses: style flags in bits 0-3, foreground colour in
+// bits 4-17 and background colour in bits 18-31.
+const STYLE_MASK: u32 = 0x0000_000f;
+const FG_MASK: u32 = 0x0003_fff0;
+const BG_MASK: u32 = 0xfffc_0000;
+
+fn ansi_bits(color: Color) -> u32 {
+    color as u32 | 0x40
+}
+
+fn argb_bits(argb: u16) -> u32 {
+    let argb = if argb < 0x100 { argb + 0x100 } else { argb } as u32;
+    ((argb >> 1) & 0x7ff) | ((argb >> 13) << 11)
+}
+
 impl Attr {
+    pub fn new(fg: Color, bg: Color) -> Attr {
+        Attr(0).with_fg(fg).with_bg(bg)
+    }
+
+    pub fn argb<F: Into<u16>, B: Into<u16>>(fg: F, bg: B) -> Attr {
+        Attr(0).with_argb_fg(fg).with_argb_bg(bg)
+    }
+
+    pub fn with_fg(self, fg: Color) -> Attr {
+        Attr((self.0 & !FG_MASK) | (ansi_bits(fg) << 4))
+    }
+
+    pub fn with_bg(self, bg: Color) -> Attr {
+        Attr((self.0 & !BG_MASK) | (ansi_bits(bg) << 18))
+    }
+
+    pub fn with_argb_fg<C: Into<u16>>(self, fg: C) -> Attr {
+        Attr((self.0 & !FG_MASK) | (argb_bits(fg.into()) << 4))
+    }
+
+    pub fn with_argb_bg<C: Into<u16>>(self, bg: C) -> Attr {
+        Attr((self.0 & !BG_MASK) | (argb_bits(bg.into()) << 18))
+    }
+
+    pub fn with_style(self, style: Style) -> Attr {
+        Attr((self.0 & !STYLE_MASK) | style.bits())
+    }
+
     pub fn ansi(&self) -> u8 {
         unsafe { caca_attr_to_ansi(self.0) }
     }
@@ -52,7 +102,7 @@ impl Attr {
     }
 
     pub fn style(&self) -> Result<Style> {
-        Style::from_bits(self.0 & 0xf).ok_or(Error::InvalidStyle)
+        Style::from_bits(self.0 & STYLE_MASK).ok_or(Error::InvalidStyle)
     }
 }
 
@@ -68,8 +118,84 @@ pub struct Argb {
     pub fg_b: u8,
 }
 
+impl Argb {
+    pub fn fg(&self) -> Rgba {
+        Rgba::new(
+            self.fg_r * 0x11,
+            self.fg_g * 0x11,
+            self.fg_b * 0x11,
+            self.fg_a * 0x11,
+        )
+    }
+
+    pub fn bg(&self) -> Rgba {
+        Rgba::new(
+            self.bg_r * 0x11,
+            self.bg_g * 0x11,
+            self.bg_b * 0x11,
+            self.bg_a * 0x11,
+        )
+    }
+}
+
 impl From<Attr> for Argb {
     fn from(attr: Attr) -> Self {
         attr.argb64()
     }
 }
+
+impl From<Argb> for Attr {
+    fn from(argb: Argb) -> Self {
+        Attr::argb(argb.fg(), argb.bg())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Attr;
+    use crate::{Boundaries, Canvas, Color, Point, Style};
+
+    #[test]
+    fn ansi() {
+        let attr = Attr::new(Color::LightRed, Color::Blue).with_style(Style::BOLD | Style::BLINK);
+
+        assert_eq!(attr.ansi_fg().unwrap(), Color::LightRed);
+        assert_eq!(attr.ansi_bg().unwrap(), Color::Blue);
+        assert_eq!(attr.style().unwrap(), Style::BOLD | Style::BLINK);
+
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 1,
+            height: 1,
+        })
+        .unwrap();
+        canvas
+            .set_color_ansi(Color::LightRed as u8, Color::Blue as u8)
+            .unwrap();
+        canvas.set_attr(Style::BOLD | Style::BLINK);
+        canvas.put_char(&Point { x: 0, y: 0 }, 'x' as u32);
+
+        assert_eq!(canvas.get_attr(&Point { x: 0, y: 0 }), attr);
+    }
+
+    #[test]
+    fn argb() {
+        let attr = Attr::argb(0xf0f0u16, 0x8a4cu16).with_style(Style::UNDERLINE);
+
+        assert_eq!(attr.rgb12_fg(), 0x0f0);
+        assert_eq!(attr.rgb12_bg(), 0xa4c);
+        assert_eq!(attr.style().unwrap(), Style::UNDERLINE);
+
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 1,
+            height: 1,
+        })
+        .unwrap();
+        canvas.set_color_argb(0xf0f0u16, 0x8a4cu16);
+        canvas.put_char(&Point { x: 0, y: 0 }, 'x' as u32);
+
+        assert_eq!(
+            canvas.get_attr(&Point { x: 0, y: 0 }),
+            attr.with_style(Style::empty())
+        );
+    }
+}
@@ -1,4 +1,16 @@
-use std::{ffi::CStr, marker::PhantomData, path::Path, ptr, str};
+use std::{
+    borrow::{Borrow, Cow},
+    env,
+    ffi::CStr,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    marker::PhantomData,
+    path::Path,
+    process, ptr,
+    slice::{self, Chunks, ChunksMut},
+    str::{self, FromStr},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use errno::errno;
 use libcaca_sys::{
@@ -7,37 +19,66 @@ use libcaca_sys::{
     caca_dither_bitmap, caca_draw_box, caca_draw_circle, caca_draw_ellipse, caca_draw_line,
     caca_draw_polyline, caca_draw_thin_box, caca_draw_thin_ellipse, caca_draw_thin_line,
     caca_draw_thin_polyline, caca_draw_thin_triangle, caca_draw_triangle, caca_enable_dirty_rect,
-    caca_fill_ellipse, caca_fill_triangle, caca_flip, caca_flop, caca_flush_figlet,
-    caca_free_canvas, caca_free_frame, caca_get_attr, caca_get_canvas_handle_x,
-    caca_get_canvas_handle_y, caca_get_canvas_height, caca_get_canvas_width, caca_get_char,
-    caca_get_dirty_rect, caca_get_dirty_rect_count, caca_get_frame_count, caca_get_frame_name,
-    caca_gotoxy, caca_invert, caca_put_attr, caca_put_char, caca_put_figchar, caca_put_str,
-    caca_remove_dirty_rect, caca_render_canvas, caca_rotate_180, caca_rotate_left,
-    caca_rotate_right, caca_set_attr, caca_set_canvas_boundaries, caca_set_canvas_handle,
-    caca_set_canvas_size, caca_set_color_ansi, caca_set_color_argb, caca_set_frame,
+    caca_export_area_to_memory, caca_export_canvas_to_memory, caca_fill_ellipse,
+    caca_fill_triangle, caca_fill_triangle_textured, caca_flip, caca_flop, caca_flush_figlet,
+    caca_free_canvas, caca_free_frame, caca_get_attr, caca_get_canvas_attrs, caca_get_canvas_chars,
+    caca_get_canvas_handle_x, caca_get_canvas_handle_y, caca_get_canvas_height,
+    caca_get_canvas_width, caca_get_char, caca_get_dirty_rect, caca_get_dirty_rect_count,
+    caca_get_export_list, caca_get_frame_count, caca_get_frame_name, caca_get_import_list,
+    caca_gotoxy, caca_import_area_from_file, caca_import_area_from_memory,
+    caca_import_canvas_from_file, caca_import_canvas_from_memory, caca_invert, caca_put_attr,
+    caca_put_char, caca_put_figchar, caca_put_str, caca_remove_dirty_rect, caca_render_canvas,
+    caca_rotate_180, caca_rotate_left, caca_rotate_right, caca_set_attr,
+    caca_set_canvas_boundaries, caca_set_canvas_handle, caca_set_canvas_size, caca_set_color_ansi,
+    caca_set_color_argb, caca_set_figfont_smush, caca_set_figfont_width, caca_set_frame,
     caca_set_frame_name, caca_stretch_left, caca_stretch_right, caca_toggle_attr, caca_unset_attr,
     caca_wherex, caca_wherey,
 };
 
+#[cfg(feature = "image")]
+use std::time::Duration;
+
+#[cfg(feature = "image")]
+use crate::decode::{self, Bitmap};
 use crate::{
-    attr::Attr, dither::Dither, error::Error, font::Font, result::Result, utils::lossy_cstring,
-    Boundaries, Circle, Ellipse, Point, Rectangle, Triangle,
+    attr::Attr,
+    dither::{
+        diffusion, edges, fit, levels, ramp, tiled, Dither, DitherStage, Fitting, GlyphRamp,
+        Levels, LineArt, Tiling,
+    },
+    error::Error,
+    figlet::Smush,
+    font::Font,
+    pixels::{self, SubCell},
+    result::Result,
+    utils::{lossy_cstring, pairs_list},
+    Boundaries, Circle, Ellipse, Point, Rectangle, TexCoord, Triangle,
 };
 
-#[doc(hidden)]
-// It does nothing. I just don't want to leak my pointers.
-pub struct CanvasHolder(pub(crate) *mut caca_canvas_t);
-
-impl CanvasHolder {
-    pub(crate) fn as_internal(&self) -> *mut caca_canvas_t {
-        self.0
-    }
+enum CanvasInner<'a> {
+    Borrowed(*mut caca_canvas_t, PhantomData<&'a mut ()>),
+    Owned(*mut caca_canvas_t),
 }
 
-pub enum Canvas<'a> {
-    Borrowed(*mut caca_canvas_t, PhantomData<&'a ()>),
-    Owned(CanvasHolder),
-}
+/// A libcaca canvas, either owned or borrowed from a [`Display`](crate::Display).
+///
+/// Every method that changes the canvas needs a mutable reference, so it's
+/// not possible to draw on it through a shared one:
+///
+/// ```compile_fail
+/// # use libcaca::{Boundaries, Canvas, Point};
+/// let canvas = Canvas::new(&Boundaries { width: 10, height: 10 }).unwrap();
+/// canvas.put_char(&Point { x: 0, y: 0 }, 'a' as u32);
+/// ```
+///
+/// A canvas is `Send` but not `Sync`, libcaca does no locking at all:
+///
+/// ```compile_fail
+/// # use libcaca::Canvas;
+/// fn is_sync<T: Sync>() {}
+/// is_sync::<Canvas>();
+/// ```
+pub struct Canvas<'a>(CanvasInner<'a>);
 
 impl<'a> Canvas<'a> {
     pub fn new(boundaries: &Boundaries) -> Result<Canvas<'a>> {
@@ -49,11 +90,11 @@ impl<'a> Canvas<'a> {
                 what => Err(Error::Unknown(what)),
             }
         } else {
-            Ok(Self::Owned(CanvasHolder(ptr)))
+            Ok(Self(CanvasInner::Owned(ptr)))
         }
     }
 
-    pub fn set_size(&self, boundaries: &Boundaries) -> Result<()> {
+    pub fn set_size(&mut self, boundaries: &Boundaries) -> Result<()> {
         if unsafe {
             caca_set_canvas_size(
                 self.as_internal(),
@@ -88,11 +129,80 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    // I don't want to play with this unsafe shit
-    // caca_get_canvas_chars
-    // caca_get_canvas_attrs
+    pub fn chars(&self) -> &[u32] {
+        let ptr = unsafe { caca_get_canvas_chars(self.as_internal()) };
+        if ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(ptr, self.width() * self.height()) }
+        }
+    }
 
-    pub fn gotoxy(&self, point: &Point) {
+    // The whole canvas is marked dirty, we can't know which cells will be
+    // touched through the returned slice.
+    pub fn chars_mut(&mut self) -> &mut [u32] {
+        let ptr = unsafe { caca_get_canvas_chars(self.as_internal()) } as *mut u32;
+        if ptr.is_null() {
+            &mut []
+        } else {
+            self.mark_dirty();
+            unsafe { slice::from_raw_parts_mut(ptr, self.width() * self.height()) }
+        }
+    }
+
+    pub fn attrs(&self) -> &[Attr] {
+        let ptr = unsafe { caca_get_canvas_attrs(self.as_internal()) } as *const Attr;
+        if ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(ptr, self.width() * self.height()) }
+        }
+    }
+
+    // Same as chars_mut, the whole canvas is marked dirty.
+    pub fn attrs_mut(&mut self) -> &mut [Attr] {
+        let ptr = unsafe { caca_get_canvas_attrs(self.as_internal()) } as *mut Attr;
+        if ptr.is_null() {
+            &mut []
+        } else {
+            self.mark_dirty();
+            unsafe { slice::from_raw_parts_mut(ptr, self.width() * self.height()) }
+        }
+    }
+
+    pub fn char_rows(&self) -> Chunks<'_, u32> {
+        let width = self.width().max(1);
+        self.chars().chunks(width)
+    }
+
+    pub fn char_rows_mut(&mut self) -> ChunksMut<'_, u32> {
+        let width = self.width().max(1);
+        self.chars_mut().chunks_mut(width)
+    }
+
+    pub fn attr_rows(&self) -> Chunks<'_, Attr> {
+        let width = self.width().max(1);
+        self.attrs().chunks(width)
+    }
+
+    pub fn attr_rows_mut(&mut self) -> ChunksMut<'_, Attr> {
+        let width = self.width().max(1);
+        self.attrs_mut().chunks_mut(width)
+    }
+
+    fn mark_dirty(&mut self) {
+        unsafe {
+            caca_add_dirty_rect(
+                self.as_internal(),
+                0,
+                0,
+                self.width() as i32,
+                self.height() as i32,
+            )
+        };
+    }
+
+    pub fn gotoxy(&mut self, point: &Point) {
         unsafe { caca_gotoxy(self.as_internal(), point.x as i32, point.y as i32) };
     }
 
@@ -104,7 +214,7 @@ impl<'a> Canvas<'a> {
         unsafe { caca_wherey(self.as_internal()) }
     }
 
-    pub fn put_char(&self, point: &Point, ch: u32) -> usize {
+    pub fn put_char(&mut self, point: &Point, ch: u32) -> usize {
         unsafe { caca_put_char(self.as_internal(), point.x as i32, point.y as i32, ch) as usize }
     }
 
@@ -112,7 +222,7 @@ impl<'a> Canvas<'a> {
         unsafe { caca_get_char(self.as_internal(), point.x as i32, point.y as i32) }
     }
 
-    pub fn put_str<S: AsRef<str>>(&self, point: &Point, s: S) -> usize {
+    pub fn put_str<S: AsRef<str>>(&mut self, point: &Point, s: S) -> usize {
         let c_str = lossy_cstring(s);
         unsafe {
             caca_put_str(
@@ -128,11 +238,11 @@ impl<'a> Canvas<'a> {
     // - caca_printf
     // - caca_vprintf
 
-    pub fn clear(&self) {
+    pub fn clear(&mut self) {
         unsafe { caca_clear_canvas(self.as_internal()) };
     }
 
-    pub fn set_handle(&self, point: Point) {
+    pub fn set_handle(&mut self, point: Point) {
         unsafe { caca_set_canvas_handle(self.as_internal(), point.x as i32, point.y as i32) };
     }
 
@@ -151,7 +261,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn blit(&self, point: &Point, src: &Canvas, mask: Option<&Canvas>) -> Result<()> {
+    pub fn blit(&mut self, point: &Point, src: &Canvas, mask: Option<&Canvas>) -> Result<()> {
         if unsafe {
             caca_blit(
                 self.as_internal(),
@@ -171,7 +281,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn set_boundaries(&self, boundaries: &Rectangle) -> Result<()> {
+    pub fn set_boundaries(&mut self, boundaries: &Rectangle) -> Result<()> {
         if unsafe {
             caca_set_canvas_boundaries(
                 self.as_internal(),
@@ -193,11 +303,11 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn disable_dirty_rect(&self) {
+    pub fn disable_dirty_rect(&mut self) {
         unsafe { caca_disable_dirty_rect(self.as_internal()) };
     }
 
-    pub fn enable_dirty_rect(&self) -> Result<()> {
+    pub fn enable_dirty_rect(&mut self) -> Result<()> {
         if unsafe { caca_enable_dirty_rect(self.as_internal()) } != 0 {
             match errno().0 {
                 // Just ignore this error and silently do nothing if dirty rects
@@ -245,7 +355,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn caca_add_dirty_rect(&self, rectangle: &Rectangle) -> Result<()> {
+    pub fn caca_add_dirty_rect(&mut self, rectangle: &Rectangle) -> Result<()> {
         if unsafe {
             caca_add_dirty_rect(
                 self.as_internal(),
@@ -265,7 +375,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn remove_dirty_rect(&self, rectangle: &Rectangle) -> Result<()> {
+    pub fn remove_dirty_rect(&mut self, rectangle: &Rectangle) -> Result<()> {
         if unsafe {
             caca_remove_dirty_rect(
                 self.as_internal(),
@@ -285,27 +395,27 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn clear_dirty_rect_list(&self) {
+    pub fn clear_dirty_rect_list(&mut self) {
         unsafe { caca_clear_dirty_rect_list(self.as_internal()) };
     }
 
-    pub fn invert(&self) {
+    pub fn invert(&mut self) {
         unsafe { caca_invert(self.as_internal()) };
     }
 
-    pub fn flip(&self) {
+    pub fn flip(&mut self) {
         unsafe { caca_flip(self.as_internal()) };
     }
 
-    pub fn flop(&self) {
+    pub fn flop(&mut self) {
         unsafe { caca_flop(self.as_internal()) };
     }
 
-    pub fn rotate_180(&self) {
+    pub fn rotate_180(&mut self) {
         unsafe { caca_rotate_180(self.as_internal()) };
     }
 
-    pub fn rotate_left(&self) -> Result<()> {
+    pub fn rotate_left(&mut self) -> Result<()> {
         if unsafe { caca_rotate_left(self.as_internal()) } != 0 {
             match errno().0 {
                 libc::EBUSY => Err(Error::CanvasInUse),
@@ -317,7 +427,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn rotate_right(&self) -> Result<()> {
+    pub fn rotate_right(&mut self) -> Result<()> {
         if unsafe { caca_rotate_right(self.as_internal()) } != 0 {
             match errno().0 {
                 libc::EBUSY => Err(Error::CanvasInUse),
@@ -329,7 +439,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn stretch_left(&self) -> Result<()> {
+    pub fn stretch_left(&mut self) -> Result<()> {
         if unsafe { caca_stretch_left(self.as_internal()) } != 0 {
             match errno().0 {
                 libc::EBUSY => Err(Error::CanvasInUse),
@@ -341,7 +451,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn stretch_right(&self) -> Result<()> {
+    pub fn stretch_right(&mut self) -> Result<()> {
         if unsafe { caca_stretch_right(self.as_internal()) } != 0 {
             match errno().0 {
                 libc::EBUSY => Err(Error::CanvasInUse),
@@ -357,19 +467,19 @@ impl<'a> Canvas<'a> {
         unsafe { caca_get_attr(self.as_internal(), point.x as i32, point.y as i32) }.into()
     }
 
-    pub fn set_attr<A: Into<Attr>>(&self, attr: A) {
+    pub fn set_attr<A: Into<Attr>>(&mut self, attr: A) {
         unsafe { caca_set_attr(self.as_internal(), attr.into().into()) };
     }
 
-    pub fn unset_attr<A: Into<Attr>>(&self, attr: A) {
+    pub fn unset_attr<A: Into<Attr>>(&mut self, attr: A) {
         unsafe { caca_unset_attr(self.as_internal(), attr.into().into()) };
     }
 
-    pub fn toggle_attr<A: Into<Attr>>(&self, attr: A) {
+    pub fn toggle_attr<A: Into<Attr>>(&mut self, attr: A) {
         unsafe { caca_toggle_attr(self.as_internal(), attr.into().into()) };
     }
 
-    pub fn put_attr<A: Into<Attr>>(&self, point: &Point, attr: A) {
+    pub fn put_attr<A: Into<Attr>>(&mut self, point: &Point, attr: A) {
         unsafe {
             caca_put_attr(
                 self.as_internal(),
@@ -380,7 +490,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn set_color_ansi(&self, fg: u8, bg: u8) -> Result<()> {
+    pub fn set_color_ansi(&mut self, fg: u8, bg: u8) -> Result<()> {
         if unsafe { caca_set_color_ansi(self.as_internal(), fg, bg) } != 0 {
             match errno().0 {
                 libc::EINVAL => Err(Error::InvalidColor),
@@ -391,11 +501,11 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn set_color_argb(&self, fg: u16, bg: u16) {
-        unsafe { caca_set_color_argb(self.as_internal(), fg, bg) };
+    pub fn set_color_argb<F: Into<u16>, B: Into<u16>>(&mut self, fg: F, bg: B) {
+        unsafe { caca_set_color_argb(self.as_internal(), fg.into(), bg.into()) };
     }
 
-    pub fn draw_line(&self, point1: &Point, point2: &Point, ch: u32) {
+    pub fn draw_line(&mut self, point1: &Point, point2: &Point, ch: u32) {
         unsafe {
             caca_draw_line(
                 self.as_internal(),
@@ -408,7 +518,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_polyline(&self, points: &[Point], ch: u32) {
+    pub fn draw_polyline(&mut self, points: &[Point], ch: u32) {
         let mut xs = Vec::with_capacity(points.len());
         let mut ys = Vec::with_capacity(points.len());
 
@@ -428,11 +538,11 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_thin_line(&self, point1: &Point, point2: &Point) {
+    pub fn draw_thin_line(&mut self, point1: &Point, point2: &Point) {
         unsafe { caca_draw_thin_line(self.as_internal(), point1.x, point1.y, point2.x, point2.y) };
     }
 
-    pub fn draw_thin_polyline(&self, points: &[Point]) {
+    pub fn draw_thin_polyline(&mut self, points: &[Point]) {
         let mut xs = Vec::with_capacity(points.len());
         let mut ys = Vec::with_capacity(points.len());
 
@@ -451,7 +561,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_circle(&self, circle: &Circle, ch: u32) {
+    pub fn draw_circle(&mut self, circle: &Circle, ch: u32) {
         unsafe {
             caca_draw_circle(
                 self.as_internal(),
@@ -463,7 +573,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_ellipse(&self, ellipse: &Ellipse, ch: u32) {
+    pub fn draw_ellipse(&mut self, ellipse: &Ellipse, ch: u32) {
         unsafe {
             caca_draw_ellipse(
                 self.as_internal(),
@@ -476,7 +586,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_thin_ellipse(&self, ellipse: &Ellipse) {
+    pub fn draw_thin_ellipse(&mut self, ellipse: &Ellipse) {
         unsafe {
             caca_draw_thin_ellipse(
                 self.as_internal(),
@@ -488,7 +598,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn fill_ellipse(&self, ellipse: &Ellipse, ch: u32) {
+    pub fn fill_ellipse(&mut self, ellipse: &Ellipse, ch: u32) {
         unsafe {
             caca_fill_ellipse(
                 self.as_internal(),
@@ -501,7 +611,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_box(&self, rect: &Rectangle, ch: u32) {
+    pub fn draw_box(&mut self, rect: &Rectangle, ch: u32) {
         unsafe {
             caca_draw_box(
                 self.as_internal(),
@@ -514,7 +624,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_thin_box(&self, rect: &Rectangle) {
+    pub fn draw_thin_box(&mut self, rect: &Rectangle) {
         unsafe {
             caca_draw_thin_box(
                 self.as_internal(),
@@ -526,7 +636,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_triangle(&self, triangle: &Triangle, ch: u32) {
+    pub fn draw_triangle(&mut self, triangle: &Triangle, ch: u32) {
         unsafe {
             caca_draw_triangle(
                 self.as_internal(),
@@ -541,7 +651,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn draw_thin_triangle(&self, triangle: &Triangle) {
+    pub fn draw_thin_triangle(&mut self, triangle: &Triangle) {
         unsafe {
             caca_draw_thin_triangle(
                 self.as_internal(),
@@ -555,7 +665,7 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    pub fn fill_triangle(&self, triangle: &Triangle, ch: u32) {
+    pub fn fill_triangle(&mut self, triangle: &Triangle, ch: u32) {
         unsafe {
             caca_fill_triangle(
                 self.as_internal(),
@@ -570,7 +680,33 @@ impl<'a> Canvas<'a> {
         };
     }
 
-    // TODO: fill_triangle_textured, idk what's uv and why it's a float array
+    // UV coordinates are normalized, (0.0, 0.0) is the top left corner of the
+    // texture and (1.0, 1.0) the bottom right one.
+    pub fn fill_triangle_textured(
+        &mut self,
+        triangle: &Triangle,
+        texture: &Canvas,
+        uv: &[TexCoord; 3],
+    ) {
+        let mut coords = [
+            triangle.vertex1.x,
+            triangle.vertex1.y,
+            triangle.vertex2.x,
+            triangle.vertex2.y,
+            triangle.vertex3.x,
+            triangle.vertex3.y,
+        ];
+        let mut uv = [uv[0].u, uv[0].v, uv[1].u, uv[1].v, uv[2].u, uv[2].v];
+
+        unsafe {
+            caca_fill_triangle_textured(
+                self.as_internal(),
+                coords.as_mut_ptr(),
+                texture.as_internal(),
+                uv.as_mut_ptr(),
+            )
+        };
+    }
 
     pub fn frame_count(&self) -> usize {
         unsafe { caca_get_frame_count(self.as_internal()) as usize }
@@ -582,7 +718,7 @@ impl<'a> Canvas<'a> {
             .to_string()
     }
 
-    pub fn set_frame(&self, id: usize) -> Result<()> {
+    pub fn set_frame(&mut self, id: usize) -> Result<()> {
         if unsafe { caca_set_frame(self.as_internal(), id as i32) } != 0 {
             match errno().0 {
                 libc::EINVAL => Err(Error::InvalidFrameIndex),
@@ -593,7 +729,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn set_frame_name<S: AsRef<str>>(&self, name: S) -> Result<()> {
+    pub fn set_frame_name<S: AsRef<str>>(&mut self, name: S) -> Result<()> {
         if unsafe { caca_set_frame_name(self.as_internal(), lossy_cstring(name).as_ptr()) } != 0 {
             match errno().0 {
                 libc::ENOMEM => Err(Error::NotEnoughMemory),
@@ -604,7 +740,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn create_frame(&self, id: usize) -> Result<()> {
+    pub fn create_frame(&mut self, id: usize) -> Result<()> {
         if unsafe { caca_create_frame(self.as_internal(), id as i32) } != 0 {
             match errno().0 {
                 libc::ENOMEM => Err(Error::NotEnoughMemory),
@@ -615,7 +751,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn free_frame(&self, id: usize) -> Result<()> {
+    pub fn free_frame(&mut self, id: usize) -> Result<()> {
         if unsafe { caca_free_frame(self.as_internal(), id as i32) } != 0 {
             match errno().0 {
                 libc::EINVAL => Err(Error::InvalidFrameIndex),
@@ -626,21 +762,58 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn set_figfont<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        if unsafe {
-            caca_canvas_set_figfont(
-                self.as_internal(),
-                path.as_ref().to_string_lossy().as_ptr() as *const _,
-            )
-        } != 0
-        {
+    // Loading a font resets the canvas size to 0x0, it grows while
+    // FIGcharacters are put on it.
+    pub fn set_figfont<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = lossy_cstring(path.as_ref().to_string_lossy());
+
+        if unsafe { caca_canvas_set_figfont(self.as_internal(), path.as_ptr()) } != 0 {
             Err(Error::InvalidFIGfont)
         } else {
             Ok(())
         }
     }
 
-    pub fn put_figchar(&self, ch: u32) -> Result<()> {
+    // libcaca only knows how to load fonts from files, so the data goes
+    // through a temporary one. It's always a new file, whatever already
+    // sits at a name (symlinks included) makes it try the next one.
+    pub fn set_figfont_from_memory<T: AsRef<[u8]>>(&mut self, data: T) -> Result<()> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let (path, mut file) = loop {
+            let path = env::temp_dir().join(format!(
+                "libcaca-figfont-{}-{}.flf",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let written = file.write_all(data.as_ref());
+        drop(file);
+        let res = written
+            .map_err(Error::from)
+            .and_then(|_| self.set_figfont(&path));
+        let _ = fs::remove_file(&path);
+
+        res
+    }
+
+    pub fn set_figfont_width(&mut self, width: usize) {
+        unsafe { caca_set_figfont_width(self.as_internal(), width as i32) };
+    }
+
+    pub fn set_figfont_smush(&mut self, smush: Smush) {
+        let smush = lossy_cstring(smush.to_string());
+        unsafe { caca_set_figfont_smush(self.as_internal(), smush.as_ptr()) };
+    }
+
+    pub fn put_figchar(&mut self, ch: u32) -> Result<()> {
         if unsafe { caca_put_figchar(self.as_internal(), ch) } != 0 {
             Err(Error::InvalidFIGfont)
         } else {
@@ -648,7 +821,7 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn flush_figlet(&self) -> Result<()> {
+    pub fn flush_figlet(&mut self) -> Result<()> {
         if unsafe { caca_flush_figlet(self.as_internal()) } != 0 {
             Err(Error::InvalidFIGfont)
         } else {
@@ -656,8 +829,19 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    pub fn dither_bitmap<T: Into<Vec<u8>>>(&self, rect: &Rectangle, dither: &Dither, image: T) {
-        let buffer = image.into();
+    pub fn put_figstr<S: AsRef<str>>(&mut self, s: S) -> Result<()> {
+        for ch in s.as_ref().chars() {
+            self.put_figchar(ch as u32)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn dither_bitmap(&mut self, rect: &Rectangle, dither: &Dither, image: &[u8]) -> Result<()> {
+        if image.len() < dither.buffer_len() {
+            return Err(Error::BufferTooSmall);
+        }
+
         unsafe {
             caca_dither_bitmap(
                 self.as_internal(),
@@ -666,19 +850,140 @@ impl<'a> Canvas<'a> {
                 rect.width as i32,
                 rect.height as i32,
                 dither.as_internal(),
-                buffer.as_ptr() as *const _,
+                image.as_ptr() as *const _,
             )
         };
+
+        Ok(())
+    }
+
+    // Colours are picked on the Rust side by `stage`, after the dither's
+    // gamma, among the ANSI colours of the dither's colour mode. Unlike
+    // `dither_bitmap` every cell is one solid colour, a full glyph of the
+    // dither's charset with the same foreground and background, so no
+    // two colours get mixed inside a cell.
+    pub fn dither_bitmap_with<S: DitherStage + ?Sized>(
+        &mut self,
+        rect: &Rectangle,
+        dither: &Dither,
+        image: &[u8],
+        stage: &S,
+    ) -> Result<()> {
+        diffusion::dither_bitmap(self, rect, dither, image, stage)
+    }
+
+    // Glyphs come from `ramp` by cell luminance instead of the dither's
+    // charset.
+    pub fn dither_bitmap_ramp(
+        &mut self,
+        rect: &Rectangle,
+        dither: &Dither,
+        image: &[u8],
+        ramp: &GlyphRamp,
+    ) -> Result<()> {
+        ramp::dither_bitmap(self, rect, dither, image, ramp)
+    }
+
+    // Remaps the bitmap's levels on the Rust side first.
+    pub fn dither_bitmap_levels(
+        &mut self,
+        rect: &Rectangle,
+        dither: &Dither,
+        image: &[u8],
+        levels: &Levels,
+    ) -> Result<()> {
+        levels::dither_bitmap(self, rect, dither, image, levels)
+    }
+
+    // Directional glyphs along the bitmap's edges, over the regular fill.
+    pub fn dither_bitmap_line_art(
+        &mut self,
+        rect: &Rectangle,
+        dither: &Dither,
+        image: &[u8],
+        line_art: &LineArt,
+    ) -> Result<()> {
+        edges::dither_bitmap(self, rect, dither, image, line_art)
+    }
+
+    // Several pixels per cell, see `SubCell`.
+    pub fn dither_bitmap_pixels(
+        &mut self,
+        rect: &Rectangle,
+        dither: &Dither,
+        image: &[u8],
+        mode: SubCell,
+    ) -> Result<()> {
+        pixels::dither_bitmap(self, rect, dither, image, mode)
+    }
+
+    // Every frame of `frames` goes to its own canvas frame, created as
+    // needed. Returns the frame delays.
+    #[cfg(feature = "image")]
+    pub fn dither_frames(
+        &mut self,
+        rect: &Rectangle,
+        frames: &[(Bitmap, Duration)],
+    ) -> Result<Vec<Duration>> {
+        decode::dither_frames(self, rect, frames)
+    }
+
+    // Decodes and dithers an image file in one go, animated GIFs included.
+    #[cfg(feature = "image")]
+    pub fn dither_image_file<P: AsRef<Path>>(
+        &mut self,
+        rect: &Rectangle,
+        path: P,
+    ) -> Result<Vec<Duration>> {
+        self.dither_frames(rect, &decode::frames_from_file(path)?)
+    }
+
+    // Splits `rect` in tiles dithered in parallel, see `Tiling`.
+    pub fn dither_bitmap_tiled(
+        &mut self,
+        rect: &Rectangle,
+        dither: &Dither,
+        image: &[u8],
+        tiling: &Tiling,
+    ) -> Result<()> {
+        tiled::dither_bitmap(self, rect, dither, image, tiling)
+    }
+
+    // Works out where the image goes inside `area`, returns the cells it
+    // was given.
+    pub fn dither_bitmap_fit(
+        &mut self,
+        area: &Rectangle,
+        dither: &Dither,
+        image: &[u8],
+        fitting: &Fitting,
+    ) -> Result<Rectangle> {
+        fit::dither_bitmap(self, area, dither, image, fitting)
     }
 
-    pub fn render<T: Into<Vec<u8>>>(
+    // Pixels are written as 32 bits ARGB, one byte per channel in that order.
+    pub fn render(
         &self,
         font: &Font,
-        buffer: T,
+        buffer: &mut [u8],
         bounds: &Boundaries,
-        pitch: i32,
+        pitch: usize,
     ) -> Result<()> {
-        let mut buffer = buffer.into();
+        let row = bounds.width.checked_mul(4).ok_or(Error::InvalidSize)?;
+        if pitch < row {
+            return Err(Error::InvalidSize);
+        }
+
+        if bounds.height > 0 {
+            // Sizes that overflow can't fit in any buffer
+            let len = pitch
+                .checked_mul(bounds.height - 1)
+                .and_then(|len| len.checked_add(row))
+                .ok_or(Error::BufferTooSmall)?;
+            if buffer.len() < len {
+                return Err(Error::BufferTooSmall);
+            }
+        }
 
         if unsafe {
             caca_render_canvas(
@@ -687,7 +992,7 @@ impl<'a> Canvas<'a> {
                 buffer.as_mut_ptr() as *mut _,
                 bounds.width as i32,
                 bounds.height as i32,
-                pitch,
+                pitch as i32,
             )
         } != 0
         {
@@ -700,39 +1005,471 @@ impl<'a> Canvas<'a> {
         }
     }
 
-    // TODO: import/export
+    pub fn render_rgba(&self, font: &Font) -> Result<RgbaImage> {
+        let width = self.width() * font.width();
+        let height = self.height() * font.height();
+        let mut data = vec![0u8; width * height * 4];
+
+        self.render(font, &mut data, &Boundaries { width, height }, width * 4)?;
+
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.rotate_left(1);
+        }
+
+        Ok(RgbaImage {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn import_from_memory<T: AsRef<[u8]>>(
+        &mut self,
+        data: T,
+        format: Option<Format>,
+    ) -> Result<usize> {
+        let data = data.as_ref();
+        let format = lossy_cstring(format.map(|f| f.to_string()).unwrap_or_default());
+
+        let read = unsafe {
+            caca_import_canvas_from_memory(
+                self.as_internal(),
+                data.as_ptr() as *const _,
+                data.len() as _,
+                format.as_ptr(),
+            )
+        };
+
+        imported(read as isize)
+    }
+
+    pub fn import_from_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        format: Option<Format>,
+    ) -> Result<usize> {
+        let path = lossy_cstring(path.as_ref().to_string_lossy());
+        let format = lossy_cstring(format.map(|f| f.to_string()).unwrap_or_default());
+
+        let read = unsafe {
+            caca_import_canvas_from_file(self.as_internal(), path.as_ptr(), format.as_ptr())
+        };
+
+        imported_from_file(read as isize)
+    }
+
+    pub fn export_to_memory(&self, format: Format) -> Result<Vec<u8>> {
+        let format = lossy_cstring(format.to_string());
+        let mut len = 0;
+
+        let buffer =
+            unsafe { caca_export_canvas_to_memory(self.as_internal(), format.as_ptr(), &mut len) };
+
+        unsafe { exported_buffer(buffer, len as usize) }
+    }
+
+    pub fn import_area_from_memory<T: AsRef<[u8]>>(
+        &mut self,
+        point: &Point,
+        data: T,
+        format: Option<Format>,
+    ) -> Result<usize> {
+        let data = data.as_ref();
+        let format = lossy_cstring(format.map(|f| f.to_string()).unwrap_or_default());
+
+        let read = unsafe {
+            caca_import_area_from_memory(
+                self.as_internal(),
+                point.x,
+                point.y,
+                data.as_ptr() as *const _,
+                data.len() as _,
+                format.as_ptr(),
+            )
+        };
+
+        imported(read as isize)
+    }
+
+    pub fn import_area_from_file<P: AsRef<Path>>(
+        &mut self,
+        point: &Point,
+        path: P,
+        format: Option<Format>,
+    ) -> Result<usize> {
+        let path = lossy_cstring(path.as_ref().to_string_lossy());
+        let format = lossy_cstring(format.map(|f| f.to_string()).unwrap_or_default());
+
+        let read = unsafe {
+            caca_import_area_from_file(
+                self.as_internal(),
+                point.x,
+                point.y,
+                path.as_ptr(),
+                format.as_ptr(),
+            )
+        };
+
+        imported_from_file(read as isize)
+    }
+
+    pub fn export_area_to_memory(&self, rect: &Rectangle, format: Format) -> Result<Vec<u8>> {
+        let format = lossy_cstring(format.to_string());
+        let mut len = 0;
+
+        let buffer = unsafe {
+            caca_export_area_to_memory(
+                self.as_internal(),
+                rect.x,
+                rect.y,
+                rect.width as i32,
+                rect.height as i32,
+                format.as_ptr(),
+                &mut len,
+            )
+        };
+
+        unsafe { exported_buffer(buffer, len as usize) }
+    }
+
+    pub fn import_formats() -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        unsafe { pairs_list(caca_get_import_list()) }
+            .into_iter()
+            .filter(|(name, _)| !name.is_empty())
+            .collect()
+    }
+
+    pub fn export_formats() -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        unsafe { pairs_list(caca_get_export_list()) }
+            .into_iter()
+            .filter(|(name, _)| !name.is_empty())
+            .collect()
+    }
+
+    pub(crate) fn borrowed(ptr: *mut caca_canvas_t) -> Canvas<'a> {
+        Self(CanvasInner::Borrowed(ptr, PhantomData))
+    }
 
     pub(crate) fn as_internal(&self) -> *mut caca_canvas_t {
-        match self {
-            Self::Owned(internal) => internal.as_internal(),
-            Self::Borrowed(ptr, _) => *ptr,
+        match self.0 {
+            CanvasInner::Owned(ptr) => ptr,
+            CanvasInner::Borrowed(ptr, _) => ptr,
         }
     }
 
     pub fn is_borrowed(&self) -> bool {
-        match self {
-            Self::Borrowed(_, _) => true,
-            Self::Owned(_) => false,
+        match self.0 {
+            CanvasInner::Borrowed(_, _) => true,
+            CanvasInner::Owned(_) => false,
         }
     }
 
     pub fn is_owned(&self) -> bool {
-        match self {
-            Self::Owned(_) => true,
-            Self::Borrowed(_, _) => false,
+        match self.0 {
+            CanvasInner::Owned(_) => true,
+            CanvasInner::Borrowed(_, _) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+fn imported(read: isize) -> Result<usize> {
+    if read < 0 {
+        match errno().0 {
+            libc::EINVAL => Err(Error::UnknownFormat),
+            libc::ENOMEM => Err(Error::NotEnoughMemory),
+            what => Err(Error::Unknown(what)),
+        }
+    } else if read == 0 {
+        Err(Error::TruncatedInput)
+    } else {
+        Ok(read as usize)
+    }
+}
+
+// Anything we don't know about comes from opening or reading the file.
+fn imported_from_file(read: isize) -> Result<usize> {
+    imported(read).map_err(|err| match err {
+        Error::Unknown(what) => Error::IO(std::io::Error::from_raw_os_error(what)),
+        err => err,
+    })
+}
+
+// Copies a buffer allocated by libcaca's exporters into a Vec and frees it.
+unsafe fn exported_buffer(buffer: *mut libc::c_void, len: usize) -> Result<Vec<u8>> {
+    if buffer.is_null() {
+        match errno().0 {
+            libc::EINVAL => Err(Error::UnknownFormat),
+            libc::ENOMEM => Err(Error::NotEnoughMemory),
+            what => Err(Error::Unknown(what)),
         }
+    } else {
+        let res = slice::from_raw_parts(buffer as *const u8, len).to_vec();
+        libc::free(buffer);
+        Ok(res)
     }
 }
 
+// libcaca keeps no thread local state about canvases, so moving one between
+// threads is fine as long as nobody else is using it at the same time.
 unsafe impl<'a> Send for Canvas<'a> {}
 
 impl<'a> Drop for Canvas<'a> {
     fn drop(&mut self) {
+        if let CanvasInner::Owned(ptr) = self.0 {
+            unsafe { caca_free_canvas(ptr) };
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Caca,
+    Ansi,
+    Utf8,
+    Utf8Cr,
+    Html,
+    Html3,
+    Bbfr,
+    Irc,
+    Ps,
+    Svg,
+    Tga,
+    Troff,
+}
+
+impl ToString for Format {
+    fn to_string(&self) -> String {
         match self {
-            Canvas::Owned(_) => {
-                unsafe { caca_free_canvas(self.as_internal()) };
-            }
-            _ => (),
+            Self::Caca => "caca",
+            Self::Ansi => "ansi",
+            Self::Utf8 => "utf8",
+            Self::Utf8Cr => "utf8cr",
+            Self::Html => "html",
+            Self::Html3 => "html3",
+            Self::Bbfr => "bbfr",
+            Self::Irc => "irc",
+            Self::Ps => "ps",
+            Self::Svg => "svg",
+            Self::Tga => "tga",
+            Self::Troff => "troff",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match raw.to_lowercase().borrow() {
+            "caca" => Ok(Self::Caca),
+            "ansi" => Ok(Self::Ansi),
+            "utf8" => Ok(Self::Utf8),
+            "utf8cr" => Ok(Self::Utf8Cr),
+            "html" => Ok(Self::Html),
+            "html3" => Ok(Self::Html3),
+            "bbfr" => Ok(Self::Bbfr),
+            "irc" => Ok(Self::Irc),
+            "ps" => Ok(Self::Ps),
+            "svg" => Ok(Self::Svg),
+            "tga" => Ok(Self::Tga),
+            "troff" => Ok(Self::Troff),
+            _ => Err(Error::UnknownFormat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Canvas, Format};
+    use crate::{error::Error, Boundaries, Color, Font, Point, Rectangle, TexCoord, Triangle};
+
+    #[test]
+    fn export_import() {
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 10,
+            height: 2,
+        })
+        .unwrap();
+        canvas.put_str(&Point { x: 0, y: 0 }, "hello");
+
+        let data = canvas.export_to_memory(Format::Caca).unwrap();
+
+        let mut other = Canvas::new(&Boundaries {
+            width: 0,
+            height: 0,
+        })
+        .unwrap();
+        assert_eq!(
+            other.import_from_memory(&data, Some(Format::Caca)).unwrap(),
+            data.len()
+        );
+        assert_eq!(other.size(), canvas.size());
+        assert_eq!(other.get_char(&Point { x: 1, y: 0 }), 'e' as u32);
+
+        assert!(matches!(
+            other.import_from_memory(&data[..data.len() / 2], Some(Format::Caca)),
+            Err(Error::TruncatedInput)
+        ));
+        assert!(matches!(
+            other.import_from_memory(&data, Some(Format::Svg)),
+            Err(Error::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn area_export_import() {
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 10,
+            height: 4,
+        })
+        .unwrap();
+        canvas.put_str(&Point { x: 2, y: 1 }, "abc");
+
+        let data = canvas
+            .export_area_to_memory(
+                &Rectangle {
+                    x: 2,
+                    y: 1,
+                    width: 3,
+                    height: 1,
+                },
+                Format::Caca,
+            )
+            .unwrap();
+
+        let mut other = Canvas::new(&Boundaries {
+            width: 10,
+            height: 4,
+        })
+        .unwrap();
+        other
+            .import_area_from_memory(&Point { x: 5, y: 2 }, &data, Some(Format::Caca))
+            .unwrap();
+
+        assert_eq!(other.size(), canvas.size());
+        assert_eq!(other.get_char(&Point { x: 5, y: 2 }), 'a' as u32);
+        assert_eq!(other.get_char(&Point { x: 7, y: 2 }), 'c' as u32);
+        assert_eq!(other.get_char(&Point { x: 2, y: 1 }), ' ' as u32);
+    }
+
+    #[test]
+    fn buffers() {
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 4,
+            height: 3,
+        })
+        .unwrap();
+        canvas.put_str(&Point { x: 1, y: 2 }, "ab");
+
+        assert_eq!(canvas.chars().len(), 12);
+        assert_eq!(canvas.attrs().len(), 12);
+        assert_eq!(canvas.char_rows().count(), 3);
+        assert_eq!(canvas.char_rows().nth(2).unwrap()[1], 'a' as u32);
+
+        canvas.clear_dirty_rect_list();
+        canvas.char_rows_mut().nth(1).unwrap()[3] = 'z' as u32;
+        assert_eq!(canvas.get_char(&Point { x: 3, y: 1 }), 'z' as u32);
+        assert_eq!(canvas.get_dirty_rect_count(), 1);
+
+        let attr = canvas.get_attr(&Point { x: 1, y: 2 });
+        canvas.attrs_mut()[0] = attr;
+        assert_eq!(canvas.get_attr(&Point { x: 0, y: 0 }), attr);
+    }
+
+    #[test]
+    fn fill_triangle_textured() {
+        let mut texture = Canvas::new(&Boundaries {
+            width: 4,
+            height: 4,
+        })
+        .unwrap();
+        texture
+            .set_color_ansi(Color::Yellow as u8, Color::Blue as u8)
+            .unwrap();
+        for y in 0..4 {
+            texture.put_str(&Point { x: 0, y }, "####");
+        }
+        let attr = texture.get_attr(&Point { x: 0, y: 0 });
+
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 10,
+            height: 10,
+        })
+        .unwrap();
+        canvas.fill_triangle_textured(
+            &Triangle {
+                vertex1: Point { x: 0, y: 0 },
+                vertex2: Point { x: 9, y: 0 },
+                vertex3: Point { x: 0, y: 9 },
+            },
+            &texture,
+            &[
+                TexCoord { u: 0.0, v: 0.0 },
+                TexCoord { u: 1.0, v: 0.0 },
+                TexCoord { u: 0.0, v: 1.0 },
+            ],
+        );
+
+        assert_eq!(canvas.get_char(&Point { x: 2, y: 2 }), '#' as u32);
+        assert_eq!(canvas.get_attr(&Point { x: 2, y: 2 }), attr);
+        assert_eq!(canvas.get_char(&Point { x: 8, y: 8 }), ' ' as u32);
+        assert_ne!(canvas.get_attr(&Point { x: 8, y: 8 }), attr);
+    }
+
+    #[test]
+    fn render() {
+        let font = Font::new(Font::list().first().unwrap()).unwrap();
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 3,
+            height: 2,
+        })
+        .unwrap();
+        canvas
+            .set_color_ansi(Color::White as u8, Color::Black as u8)
+            .unwrap();
+        canvas.clear();
+        canvas.put_str(&Point { x: 0, y: 0 }, "#");
+
+        let image = canvas.render_rgba(&font).unwrap();
+        assert_eq!(image.width, 3 * font.width());
+        assert_eq!(image.height, 2 * font.height());
+        assert_eq!(image.data.len(), image.width * image.height * 4);
+        assert!(image.data.chunks(4).all(|pixel| pixel[3] == 0xff));
+
+        let bounds = Boundaries {
+            width: image.width,
+            height: image.height,
+        };
+        let mut short = vec![0u8; image.data.len() - 1];
+        assert!(matches!(
+            canvas.render(&font, &mut short, &bounds, image.width * 4),
+            Err(Error::BufferTooSmall)
+        ));
+        assert!(matches!(
+            canvas.render(&font, &mut short, &bounds, image.width),
+            Err(Error::InvalidSize)
+        ));
+        assert!(matches!(
+            canvas.render(&font, &mut short, &bounds, usize::MAX / 2),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn formats() {
+        let exports = Canvas::export_formats();
+        assert!(exports.iter().any(|(name, _)| name == "caca"));
+
+        for (name, _) in Canvas::import_formats() {
+            let _ = name.parse::<Format>();
         }
     }
 }
@@ -0,0 +1,172 @@
+use std::{convert::TryFrom, str::FromStr};
+
+use crate::{attr::Attr, error::Error, Color};
+
+// libcaca's own palette for the 16 ANSI colours (its `ansitab16`), as
+// 0xARGB values.
+const ANSI_PALETTE: [u16; 16] = [
+    0xf000, 0xf00a, 0xf0a0, 0xf0aa, 0xfa00, 0xfa0a, 0xfa50, 0xfaaa, 0xf555, 0xf55f, 0xf5f5, 0xf5ff,
+    0xff55, 0xff5f, 0xfff5, 0xffff,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Default for Rgba {
+    fn default() -> Self {
+        Self::rgb(0, 0, 0)
+    }
+}
+
+impl Rgba {
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Rgba {
+        Rgba { r, g, b, a }
+    }
+
+    pub fn rgb(r: u8, g: u8, b: u8) -> Rgba {
+        Self::new(r, g, b, 0xff)
+    }
+
+    pub fn from_argb4444(argb: u16) -> Rgba {
+        let nibble = |shift: u16| ((argb >> shift) & 0xf) as u8 * 0x11;
+
+        Self::new(nibble(8), nibble(4), nibble(0), nibble(12))
+    }
+
+    pub fn argb4444(&self) -> u16 {
+        let nibble = |channel: u8| (channel as u16 + 8) / 0x11;
+
+        (nibble(self.a) << 12) | (nibble(self.r) << 8) | (nibble(self.g) << 4) | nibble(self.b)
+    }
+
+    pub fn with_alpha(self, a: u8) -> Rgba {
+        Rgba { a, ..self }
+    }
+
+    // Rec. 709 weights, alpha ignored.
+    pub fn luma(&self) -> u8 {
+        ((54 * self.r as u32 + 183 * self.g as u32 + 19 * self.b as u32) >> 8) as u8
+    }
+
+    // Uses libcaca's own lookup, the same one used by the ANSI drivers and
+    // exporters.
+    pub fn nearest_ansi(&self) -> Color {
+        Attr::argb(*self, *self).ansi_fg().unwrap_or(Color::Default)
+    }
+}
+
+impl From<Rgba> for u16 {
+    fn from(color: Rgba) -> Self {
+        color.argb4444()
+    }
+}
+
+impl TryFrom<Color> for Rgba {
+    type Error = Error;
+
+    fn try_from(color: Color) -> std::result::Result<Self, <Self as TryFrom<Color>>::Error> {
+        match color {
+            Color::Default => Err(Error::InvalidColor),
+            Color::Transparent => Ok(Self::new(0, 0, 0, 0)),
+            color => Ok(Self::from_argb4444(ANSI_PALETTE[color as usize])),
+        }
+    }
+}
+
+impl ToString for Rgba {
+    fn to_string(&self) -> String {
+        if self.a == 0xff {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
+    }
+}
+
+// Accepts the CSS forms: #rgb, #rgba, #rrggbb and #rrggbbaa, with or without
+// the leading #.
+impl FromStr for Rgba {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        let hex = raw.trim().trim_start_matches('#');
+
+        // Checked here so the slicing below never splits a multibyte char
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::InvalidColor);
+        }
+
+        let short = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 1], 16)
+                .map(|n| n * 0x11)
+                .map_err(|_| Error::InvalidColor)
+        };
+        let long = |i: usize| {
+            u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| Error::InvalidColor)
+        };
+
+        match hex.len() {
+            3 => Ok(Self::rgb(short(0)?, short(1)?, short(2)?)),
+            4 => Ok(Self::new(short(0)?, short(1)?, short(2)?, short(3)?)),
+            6 => Ok(Self::rgb(long(0)?, long(1)?, long(2)?)),
+            8 => Ok(Self::new(long(0)?, long(1)?, long(2)?, long(3)?)),
+            _ => Err(Error::InvalidColor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rgba;
+    use crate::Color;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn argb4444() {
+        assert_eq!(Rgba::from_argb4444(0xf08c), Rgba::rgb(0x00, 0x88, 0xcc));
+        assert_eq!(Rgba::rgb(0x00, 0x88, 0xcc).argb4444(), 0xf08c);
+        assert_eq!(Rgba::rgb(0x07, 0x09, 0xff).argb4444(), 0xf01f);
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!("#0f8".parse::<Rgba>().unwrap(), Rgba::rgb(0x00, 0xff, 0x88));
+        assert_eq!(
+            "12345678".parse::<Rgba>().unwrap(),
+            Rgba::new(0x12, 0x34, 0x56, 0x78)
+        );
+        assert!("#12345".parse::<Rgba>().is_err());
+        assert!("#gggggg".parse::<Rgba>().is_err());
+
+        assert_eq!(Rgba::rgb(0xde, 0xad, 0x0f).to_string(), "#dead0f");
+        assert_eq!(Rgba::new(1, 2, 3, 4).to_string(), "#01020304");
+    }
+
+    #[test]
+    fn ansi() {
+        for &color in &[Color::Black, Color::Red, Color::LightCyan, Color::White] {
+            assert_eq!(Rgba::try_from(color).unwrap().nearest_ansi(), color);
+        }
+
+        assert_eq!(Rgba::rgb(0xf0, 0x10, 0x20).nearest_ansi(), Color::LightRed);
+
+        assert_eq!(
+            Rgba::try_from(Color::Brown).unwrap(),
+            Rgba::rgb(0xaa, 0x55, 0x00)
+        );
+        assert_eq!(
+            Rgba::try_from(Color::LightGray).unwrap(),
+            Rgba::rgb(0xaa, 0xaa, 0xaa)
+        );
+        assert_eq!(
+            Rgba::try_from(Color::DarkGray).unwrap(),
+            Rgba::rgb(0x55, 0x55, 0x55)
+        );
+        assert!(Rgba::try_from(Color::Default).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::{fs, io::Cursor, path::Path, time::Duration};
+
+use image::{codecs::gif::GifDecoder, AnimationDecoder, DynamicImage, ImageError, ImageFormat};
+
+use crate::{
+    canvas::Canvas,
+    dither::{Dither, PixelFormat},
+    error::Error,
+    result::Result,
+    Rectangle,
+};
+
+// A decoded image, with a dither matching its pixel layout.
+pub struct Bitmap {
+    dither: Dither,
+    data: Vec<u8>,
+}
+
+impl Bitmap {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Bitmap> {
+        Self::from_memory(fs::read(path)?)
+    }
+
+    // The format is guessed from the data.
+    pub fn from_memory<T: AsRef<[u8]>>(data: T) -> Result<Bitmap> {
+        image::load_from_memory(data.as_ref())
+            .map_err(decoding_error)
+            .and_then(Self::from_image)
+    }
+
+    // Grayscale, RGB and RGBA 8 bits images are used as is, anything else
+    // is converted to RGBA first.
+    pub fn from_image(image: DynamicImage) -> Result<Bitmap> {
+        let (format, width, height, data) = match image {
+            DynamicImage::ImageLuma8(image) => (
+                PixelFormat::Gray8,
+                image.width(),
+                image.height(),
+                image.into_raw(),
+            ),
+            DynamicImage::ImageRgb8(image) => (
+                PixelFormat::Rgb24,
+                image.width(),
+                image.height(),
+                image.into_raw(),
+            ),
+            DynamicImage::ImageRgba8(image) => (
+                PixelFormat::Rgba32,
+                image.width(),
+                image.height(),
+                image.into_raw(),
+            ),
+            image => {
+                let image = image.to_rgba8();
+                (
+                    PixelFormat::Rgba32,
+                    image.width(),
+                    image.height(),
+                    image.into_raw(),
+                )
+            }
+        };
+
+        let dither = Dither::builder(format, width as usize, height as usize).build()?;
+
+        Ok(Bitmap { dither, data })
+    }
+
+    pub fn width(&self) -> usize {
+        self.dither.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.dither.height()
+    }
+
+    pub fn dither(&self) -> &Dither {
+        &self.dither
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_parts(self) -> (Dither, Vec<u8>) {
+        (self.dither, self.data)
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, rect: &Rectangle) -> Result<()> {
+        canvas.dither_bitmap(rect, &self.dither, &self.data)
+    }
+}
+
+// Every frame of an animated GIF, fully composed, with how long it stays
+// on screen. Other formats come back as a single frame.
+pub fn frames_from_memory<T: AsRef<[u8]>>(data: T) -> Result<Vec<(Bitmap, Duration)>> {
+    let data = data.as_ref();
+
+    if image::guess_format(data).map_err(decoding_error)? != ImageFormat::Gif {
+        return Ok(vec![(Bitmap::from_memory(data)?, Duration::default())]);
+    }
+
+    GifDecoder::new(Cursor::new(data))
+        .map_err(decoding_error)?
+        .into_frames()
+        .map(|frame| {
+            let frame = frame.map_err(decoding_error)?;
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            let delay =
+                Duration::from_micros(numerator as u64 * 1000 / (denominator as u64).max(1));
+
+            Ok((
+                Bitmap::from_image(DynamicImage::ImageRgba8(frame.into_buffer()))?,
+                delay,
+            ))
+        })
+        .collect()
+}
+
+pub fn frames_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<(Bitmap, Duration)>> {
+    frames_from_memory(fs::read(path)?)
+}
+
+// Dithers every frame to the same area of its own canvas frame, the first
+// frame ends up selected. Returns the frame delays.
+pub(crate) fn dither_frames(
+    canvas: &mut Canvas,
+    rect: &Rectangle,
+    frames: &[(Bitmap, Duration)],
+) -> Result<Vec<Duration>> {
+    for (id, (bitmap, _)) in frames.iter().enumerate() {
+        if id >= canvas.frame_count() {
+            canvas.create_frame(id)?;
+        }
+        canvas.set_frame(id)?;
+        bitmap.draw(canvas, rect)?;
+    }
+    canvas.set_frame(0)?;
+
+    Ok(frames.iter().map(|&(_, delay)| delay).collect())
+}
+
+fn decoding_error(error: ImageError) -> Error {
+    match error {
+        ImageError::IoError(error) => Error::IO(error),
+        ImageError::Unsupported(_) => Error::UnknownFormat,
+        error => Error::InvalidImage(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frames_from_memory, Bitmap};
+    use crate::{dither::PixelFormat, Boundaries, Canvas, Rectangle};
+    use image::{
+        codecs::{
+            gif::GifEncoder,
+            pnm::{PnmSubtype, SampleEncoding},
+        },
+        Delay, DynamicImage, Frame, GrayImage, ImageOutputFormat, RgbaImage,
+    };
+    use std::{io::Cursor, time::Duration};
+
+    #[test]
+    fn formats() {
+        let image =
+            DynamicImage::ImageLuma8(GrayImage::from_fn(4, 2, |x, _| [x as u8 * 64].into()));
+
+        for format in [
+            ImageOutputFormat::Png,
+            ImageOutputFormat::Bmp,
+            ImageOutputFormat::Pnm(PnmSubtype::Graymap(SampleEncoding::Binary)),
+        ] {
+            let mut encoded = Cursor::new(Vec::new());
+            image.write_to(&mut encoded, format).unwrap();
+
+            let bitmap = Bitmap::from_memory(encoded.get_ref()).unwrap();
+            assert_eq!((bitmap.width(), bitmap.height()), (4, 2));
+            assert_eq!(bitmap.data().len(), bitmap.dither().buffer_len());
+        }
+
+        assert!(Bitmap::from_memory(b"not an image").is_err());
+    }
+
+    #[test]
+    fn gif_frames() {
+        let mut encoded = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut encoded);
+            for shade in &[0u8, 255] {
+                let buffer = RgbaImage::from_pixel(2, 2, [*shade, *shade, *shade, 255].into());
+                let delay = Delay::from_saturating_duration(Duration::from_millis(100));
+                encoder
+                    .encode_frame(Frame::from_parts(buffer, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+
+        let frames = frames_from_memory(&encoded).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1, Duration::from_millis(100));
+        assert_eq!(frames[0].0.dither().bpp(), PixelFormat::Rgba32.bpp());
+
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 4,
+            height: 2,
+        })
+        .unwrap();
+        let rect = Rectangle {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 2,
+        };
+        let delays = canvas.dither_frames(&rect, &frames).unwrap();
+
+        assert_eq!(delays.len(), 2);
+        assert_eq!(canvas.frame_count(), 2);
+    }
+}
@@ -1,45 +1,125 @@
 use errno::errno;
 use libcaca_sys::{
     caca_canvas_t, caca_create_display, caca_create_display_with_driver, caca_display_t,
-    caca_event, caca_free_display, caca_get_canvas, caca_get_display_driver,
-    caca_get_display_driver_list, caca_get_display_height, caca_get_display_time,
-    caca_get_display_width, caca_get_event, caca_refresh_display, caca_set_cursor,
-    caca_set_display_time, caca_set_display_title, caca_set_mouse,
+    caca_event, caca_free_display, caca_get_canvas, caca_get_canvas_height, caca_get_canvas_width,
+    caca_get_display_driver, caca_get_display_driver_list, caca_get_display_height,
+    caca_get_display_time, caca_get_display_width, caca_get_event, caca_refresh_display,
+    caca_set_cursor, caca_set_display_driver, caca_set_display_time, caca_set_display_title,
+    caca_set_mouse,
 };
 
 use crate::{
-    canvas::Canvas, error::Error, event::Event, event::EventMask, result::Result,
-    utils::lossy_cstring, Boundaries,
+    canvas::Canvas,
+    error::Error,
+    event::Event,
+    event::EventMask,
+    result::Result,
+    utils::{lossy_cstring, pairs_list},
+    Boundaries,
+};
+use std::{
+    borrow::{Borrow, Cow},
+    ffi::CStr,
+    mem, ptr,
+    str::FromStr,
+    time::Duration,
 };
-use std::{borrow::Cow, ffi::CStr, marker::PhantomData, mem, ptr, time::Duration};
 
-pub struct Display<'a>(*mut caca_display_t, Option<Canvas<'a>>);
+/// A libcaca display.
+///
+/// The canvas returned by [`Display::canvas`] borrows the display mutably, it
+/// can't outlive it:
+///
+/// ```compile_fail
+/// # use libcaca::Display;
+/// let canvas = {
+///     let mut display = Display::new_with_driver(None, "null").unwrap();
+///     display.canvas()
+/// };
+/// ```
+///
+/// and there can't be two of them at the same time:
+///
+/// ```compile_fail
+/// # use libcaca::Display;
+/// let mut display = Display::new_with_driver(None, "null").unwrap();
+/// let first = display.canvas();
+/// let second = display.canvas();
+/// drop(first);
+/// ```
+///
+/// Some drivers (gl, ncurses, conio) are bound to the thread that created
+/// them, so a display can't be sent to another thread:
+///
+/// ```compile_fail
+/// # use libcaca::Display;
+/// fn is_send<T: Send>() {}
+/// is_send::<Display>();
+/// ```
+pub struct Display<'a> {
+    internal: *mut caca_display_t,
+    // Never read, keeps a canvas given by the caller alive until after
+    // `caca_free_display`
+    _canvas: Option<Canvas<'a>>,
+    // Set when `set_driver` left the display without a driver
+    driver_lost: bool,
+}
 
 impl<'a> Display<'a> {
     pub fn new(canvas: Option<Canvas>) -> Result<Display> {
         let (c_ptr, canvas) = Self::ptr_and_canvas(canvas)?;
-        let internal = unsafe { caca_create_display(c_ptr) };
+        let internal = Self::create(c_ptr, None)?;
 
-        Ok(Display(internal, canvas))
+        Ok(Display::wrap(internal, canvas))
     }
 
     pub fn new_with_driver<S: AsRef<str>>(
         canvas: Option<Canvas>,
         driver_name: S,
     ) -> Result<Display> {
-        let driver_name = lossy_cstring(driver_name);
         let (c_ptr, canvas) = Self::ptr_and_canvas(canvas)?;
-        let internal = unsafe { caca_create_display_with_driver(c_ptr, driver_name.as_ptr()) };
+        let internal = Self::create(c_ptr, Some(driver_name.as_ref()))?;
+
+        Ok(Display::wrap(internal, canvas))
+    }
+
+    fn wrap(internal: *mut caca_display_t, canvas: Option<Canvas<'a>>) -> Display<'a> {
+        Display {
+            internal,
+            _canvas: canvas,
+            driver_lost: false,
+        }
+    }
 
-        Ok(Display(internal, canvas))
+    pub fn builder() -> DisplayBuilder<'a> {
+        DisplayBuilder::new()
+    }
+
+    // Without a driver libcaca picks one, honouring $CACA_DRIVER.
+    fn create(canvas: *mut caca_canvas_t, driver: Option<&str>) -> Result<*mut caca_display_t> {
+        let internal = match driver {
+            Some(driver) => {
+                let driver = lossy_cstring(driver);
+                unsafe { caca_create_display_with_driver(canvas, driver.as_ptr()) }
+            }
+            None => unsafe { caca_create_display(canvas) },
+        };
+
+        if internal.is_null() {
+            match errno().0 {
+                libc::ENOMEM => Err(Error::NotEnoughMemory),
+                libc::ENODEV => Err(Error::FailedToOpenGraphicsDevice),
+                what => Err(Error::Unknown(what)),
+            }
+        } else {
+            Ok(internal)
+        }
     }
 
     fn ptr_and_canvas(canvas: Option<Canvas>) -> Result<(*mut caca_canvas_t, Option<Canvas>)> {
         match canvas {
-            Some(canvas) => match canvas {
-                Canvas::Borrowed(_, _) => Err(Error::CanvasInUse),
-                Canvas::Owned(_) => Ok((canvas.as_internal(), Some(canvas))),
-            },
+            Some(canvas) if canvas.is_borrowed() => Err(Error::CanvasInUse),
+            Some(canvas) => Ok((canvas.as_internal(), Some(canvas))),
             None => Ok((ptr::null_mut(), None)),
         }
     }
@@ -49,23 +129,62 @@ impl<'a> Display<'a> {
         unsafe { CStr::from_ptr(driver) }.to_string_lossy()
     }
 
-    pub fn drivers() -> Vec<Cow<'static, str>> {
-        let mut list = unsafe { caca_get_display_driver_list() };
-        let mut res = Vec::new();
+    // Switches drivers on the live display, the canvas is kept. libcaca
+    // stops the current driver first, so if the new one fails to start the
+    // previous one is brought back, or the null driver if that fails too.
+    // When even that fails `Error::DisplayDriverLost` is returned and the
+    // display is unusable: anything but dropping it is undefined, and
+    // dropping it leaks it.
+    pub fn set_driver(&mut self, driver: DisplayDriver) -> Result<()> {
+        self.set_driver_name(driver.to_string())
+    }
 
-        while !(unsafe { *list }).is_null() {
-            let driver = unsafe { *list };
-            res.push(unsafe { CStr::from_ptr(driver) }.to_string_lossy());
+    pub fn set_driver_name<S: AsRef<str>>(&mut self, name: S) -> Result<()> {
+        let name = name.as_ref();
+        if !Self::drivers().iter().any(|driver| driver == name) {
+            return Err(Error::UnknownDisplayDriver);
+        }
+
+        let previous = self.driver().into_owned();
+
+        if self.install(name) {
+            return Ok(());
+        }
+        let error = match errno().0 {
+            libc::ENODEV => Error::FailedToOpenGraphicsDevice,
+            what => Error::Unknown(what),
+        };
 
-            list = unsafe { list.add(mem::size_of::<*const i8>()) };
+        if self.install(&previous) || self.install("null") {
+            Err(error)
+        } else {
+            self.driver_lost = true;
+            Err(Error::DisplayDriverLost)
         }
+    }
+
+    fn install(&mut self, name: &str) -> bool {
+        let name = lossy_cstring(name);
+        unsafe { caca_set_display_driver(self.as_internal(), name.as_ptr()) == 0 }
+    }
 
-        res.into_iter().filter(|x| !x.is_empty()).collect()
+    // Names of the drivers the linked libcaca was built with.
+    pub fn drivers() -> Vec<Cow<'static, str>> {
+        Self::driver_list()
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| !name.is_empty())
+            .collect()
     }
 
-    pub fn canvas(&'a self) -> Canvas<'a> {
+    // Names and descriptions of the drivers the linked libcaca was built with.
+    pub fn driver_list() -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        unsafe { pairs_list(caca_get_display_driver_list()) }
+    }
+
+    pub fn canvas(&mut self) -> Canvas<'_> {
         let ptr = unsafe { caca_get_canvas(self.as_internal()) };
-        Canvas::Borrowed(ptr, PhantomData)
+        Canvas::borrowed(ptr)
     }
 
     pub fn refresh(&self) {
@@ -104,6 +223,16 @@ impl<'a> Display<'a> {
         }
     }
 
+    // Width over height of a cell, from the display's pixel size and its
+    // canvas' size.
+    pub fn cell_aspect(&self) -> f32 {
+        let canvas = unsafe { caca_get_canvas(self.as_internal()) };
+        let columns = unsafe { caca_get_canvas_width(canvas) }.max(1) as f32;
+        let rows = unsafe { caca_get_canvas_height(canvas) }.max(1) as f32;
+
+        (self.width() as f32 / columns) / (self.height().max(1) as f32 / rows)
+    }
+
     pub fn set_title<S: AsRef<str>>(&self, title: S) -> Result<()> {
         let title = lossy_cstring(title);
 
@@ -178,21 +307,214 @@ impl<'a> Display<'a> {
     }
 
     pub(crate) fn as_internal(&self) -> *mut caca_display_t {
-        self.0
+        self.internal
     }
 }
 
-unsafe impl<'a> Send for Display<'a> {}
-
 impl<'a> Drop for Display<'a> {
     fn drop(&mut self) {
+        // Freeing would call into the driver libcaca already uninstalled
+        if self.driver_lost {
+            return;
+        }
         unsafe { caca_free_display(self.as_internal()) };
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DisplayDriver {
+    Ncurses,
+    Slang,
+    X11,
+    Gl,
+    Raw,
+    Null,
+    Conio,
+}
+
+impl DisplayDriver {
+    pub fn all() -> &'static [DisplayDriver] {
+        &[
+            Self::Ncurses,
+            Self::Slang,
+            Self::X11,
+            Self::Gl,
+            Self::Raw,
+            Self::Null,
+            Self::Conio,
+        ]
+    }
+
+    // Whether the linked libcaca was built with it, it can still fail to
+    // start (no X server, no terminal...).
+    pub fn is_available(&self) -> bool {
+        let name = self.to_string();
+        Display::drivers().iter().any(|driver| *driver == name)
+    }
+}
+
+impl ToString for DisplayDriver {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Ncurses => "ncurses",
+            Self::Slang => "slang",
+            Self::X11 => "x11",
+            Self::Gl => "gl",
+            Self::Raw => "raw",
+            Self::Null => "null",
+            Self::Conio => "conio",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for DisplayDriver {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match raw.to_lowercase().borrow() {
+            "ncurses" => Ok(Self::Ncurses),
+            "slang" => Ok(Self::Slang),
+            "x11" => Ok(Self::X11),
+            "gl" => Ok(Self::Gl),
+            "raw" => Ok(Self::Raw),
+            "null" => Ok(Self::Null),
+            "conio" => Ok(Self::Conio),
+            _ => Err(Error::UnknownDisplayDriver),
+        }
+    }
+}
+
+pub struct DisplayBuilder<'a> {
+    canvas: Option<Canvas<'a>>,
+    drivers: Vec<DisplayDriver>,
+    title: Option<String>,
+    time: Option<Duration>,
+    mouse: Option<bool>,
+    cursor: Option<bool>,
+}
+
+impl<'a> Default for DisplayBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> DisplayBuilder<'a> {
+    pub fn new() -> DisplayBuilder<'a> {
+        DisplayBuilder {
+            canvas: None,
+            drivers: Vec::new(),
+            title: None,
+            time: None,
+            mouse: None,
+            cursor: None,
+        }
+    }
+
+    // Otherwise the display creates its own.
+    pub fn canvas(mut self, canvas: Canvas<'a>) -> Self {
+        self.canvas = Some(canvas);
+        self
+    }
+
+    // Drivers are tried in the order they were added, with none libcaca
+    // picks one itself.
+    pub fn driver(mut self, driver: DisplayDriver) -> Self {
+        self.drivers.push(driver);
+        self
+    }
+
+    pub fn drivers<I: IntoIterator<Item = DisplayDriver>>(mut self, drivers: I) -> Self {
+        self.drivers.extend(drivers);
+        self
+    }
+
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn refresh_delay(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn mouse(mut self, visible: bool) -> Self {
+        self.mouse = Some(visible);
+        self
+    }
+
+    pub fn cursor(mut self, visible: bool) -> Self {
+        self.cursor = Some(visible);
+        self
+    }
+
+    // Title, mouse and cursor are left alone by the drivers that don't
+    // support them. The canvas is consumed either way, on error it's dropped
+    // along with the builder.
+    pub fn build(self) -> Result<Display<'a>> {
+        let (c_ptr, canvas) = Display::ptr_and_canvas(self.canvas)?;
+        let available = Display::drivers();
+
+        let internal = if self.drivers.is_empty() {
+            Display::create(c_ptr, None)?
+        } else {
+            let (mut tried, mut unavailable) = (Vec::new(), Vec::new());
+            let mut found = None;
+
+            for driver in &self.drivers {
+                let name = driver.to_string();
+                if !available.iter().any(|driver| *driver == name) {
+                    unavailable.push(name);
+                    continue;
+                }
+
+                match Display::create(c_ptr, Some(&name)) {
+                    Ok(internal) => {
+                        found = Some(internal);
+                        break;
+                    }
+                    Err(Error::FailedToOpenGraphicsDevice) => tried.push(name),
+                    Err(error) => return Err(error),
+                }
+            }
+
+            found.ok_or(Error::NoDisplayDriver { tried, unavailable })?
+        };
+        let display = Display::wrap(internal, canvas);
+
+        if let Some(time) = self.time {
+            display.set_time(time)?;
+        }
+        if let Some(title) = self.title {
+            match display.set_title(title) {
+                Err(Error::WindowTitleUnsupported) => {}
+                result => result?,
+            }
+        }
+        if let Some(mouse) = self.mouse {
+            match display.set_mouse(mouse as i32) {
+                Err(Error::MousePointerUnsupported) => {}
+                result => result?,
+            }
+        }
+        if let Some(cursor) = self.cursor {
+            match display.set_cursor(cursor as i32) {
+                Err(Error::MouseCursorUnsupported) => {}
+                result => result?,
+            }
+        }
+
+        Ok(display)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Display;
+    use super::{Display, DisplayDriver};
+    use crate::{error::Error, Point};
+    use std::time::Duration;
 
     #[test]
     fn drivers() {
@@ -202,4 +524,65 @@ mod tests {
             Display::new_with_driver(None, "raw").unwrap().driver()
         );
     }
+
+    #[test]
+    fn builder() {
+        for driver in DisplayDriver::all() {
+            assert_eq!(
+                driver.to_string().parse::<DisplayDriver>().unwrap(),
+                *driver
+            );
+        }
+        assert!(DisplayDriver::Null.is_available());
+
+        let display = Display::builder()
+            .driver(DisplayDriver::Null)
+            .title("builder")
+            .refresh_delay(Duration::from_millis(40))
+            .mouse(false)
+            .cursor(false)
+            .build()
+            .unwrap();
+        assert_eq!(display.driver(), "null");
+        assert_eq!(display.time(), Duration::from_millis(40));
+
+        // Unavailable or failing drivers are skipped
+        let display = Display::builder()
+            .drivers(vec![DisplayDriver::Conio, DisplayDriver::Null])
+            .build()
+            .unwrap();
+        assert_eq!(display.driver(), "null");
+
+        match Display::builder().driver(DisplayDriver::Conio).build() {
+            Err(Error::NoDisplayDriver { tried, unavailable }) => {
+                assert!(tried.is_empty());
+                assert_eq!(unavailable, vec!["conio".to_string()]);
+            }
+            _ => panic!("conio shouldn't start here"),
+        }
+
+        assert!(Display::new_with_driver(None, "nonexistent").is_err());
+    }
+
+    #[test]
+    fn set_driver() {
+        let mut display = Display::new_with_driver(None, "raw").unwrap();
+        display.canvas().put_str(&Point { x: 1, y: 0 }, "kept");
+        let size = display.canvas().size();
+
+        display.set_driver(DisplayDriver::Null).unwrap();
+        assert_eq!(display.driver(), "null");
+        display.refresh();
+
+        display.set_driver_name("raw").unwrap();
+        assert_eq!(display.driver(), "raw");
+        assert_eq!(display.canvas().size(), size);
+        assert_eq!(display.canvas().chars()[1], 'k' as u32);
+
+        assert!(matches!(
+            display.set_driver_name("nonexistent"),
+            Err(Error::UnknownDisplayDriver)
+        ));
+        assert_eq!(display.driver(), "raw");
+    }
 }
@@ -1,18 +1,55 @@
-use std::{borrow::Borrow, ffi::CStr, str::FromStr};
+use std::{
+    borrow::{Borrow, Cow},
+    cell::RefCell,
+    ffi::CStr,
+    ops::{Index, IndexMut},
+    str::FromStr,
+};
 
 use errno::errno;
 use libcaca_sys::{
     caca_create_dither, caca_dither_t, caca_free_dither, caca_get_dither_algorithm,
-    caca_get_dither_antialias, caca_get_dither_brightness, caca_get_dither_charset,
-    caca_get_dither_color, caca_get_dither_contrast, caca_get_dither_gamma,
-    caca_set_dither_algorithm, caca_set_dither_antialias, caca_set_dither_brightness,
-    caca_set_dither_charset, caca_set_dither_color, caca_set_dither_contrast,
-    caca_set_dither_gamma, caca_set_dither_palette,
+    caca_get_dither_algorithm_list, caca_get_dither_antialias, caca_get_dither_antialias_list,
+    caca_get_dither_brightness, caca_get_dither_charset, caca_get_dither_charset_list,
+    caca_get_dither_color, caca_get_dither_color_list, caca_get_dither_contrast,
+    caca_get_dither_gamma, caca_set_dither_algorithm, caca_set_dither_antialias,
+    caca_set_dither_brightness, caca_set_dither_charset, caca_set_dither_color,
+    caca_set_dither_contrast, caca_set_dither_gamma, caca_set_dither_palette,
 };
 
-use crate::{error::Error, result::Result, utils::lossy_cstring};
+use crate::{
+    color::Rgba,
+    error::Error,
+    result::Result,
+    utils::{lossy_cstring, pairs_list},
+};
 
-pub struct Dither(*mut caca_dither_t);
+pub mod diffusion;
+pub mod edges;
+pub mod fit;
+pub mod levels;
+pub mod ramp;
+pub mod settings;
+pub mod tiled;
+
+pub use diffusion::{BlueNoise, Diffusion, DitherStage};
+pub use edges::{EdgeDetector, EdgeGlyphs, LineArt};
+pub use fit::{Fit, Fitting};
+pub use levels::{Adjustments, Histogram, Levels};
+pub use ramp::GlyphRamp;
+pub use settings::{DitherSettings, Preset};
+pub use tiled::Tiling;
+
+pub struct Dither {
+    internal: *mut caca_dither_t,
+    bpp: usize,
+    width: usize,
+    height: usize,
+    pitch: usize,
+    masks: [u32; 4],
+    // Kept to read back 8 bits pixels on the Rust side.
+    palette: RefCell<Option<Palette>>,
+}
 
 impl Dither {
     pub fn new(
@@ -35,24 +72,74 @@ impl Dither {
                 what => Err(Error::Unknown(what)),
             }
         } else {
-            Ok(Dither(raw_dither))
+            Ok(Dither {
+                internal: raw_dither,
+                bpp: bpp as usize,
+                width: width as usize,
+                height: height as usize,
+                pitch: pitch as usize,
+                masks: [rmask, gmask, bmask, amask],
+                palette: RefCell::new(None),
+            })
         }
     }
 
-    pub fn set_palette(
-        &self,
-        red: &[u32],
-        green: &[u32],
-        blue: &[u32],
-        alpha: &[u32],
-    ) -> Result<()> {
+    pub fn bpp(&self) -> usize {
+        self.bpp
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pitch(&self) -> usize {
+        self.pitch
+    }
+
+    // Minimum size of a bitmap with this geometry, the last row doesn't need
+    // its padding. `usize::MAX` when that overflows, no buffer is big enough.
+    pub fn buffer_len(&self) -> usize {
+        if self.height == 0 {
+            0
+        } else {
+            self.pitch
+                .checked_mul(self.height - 1)
+                .and_then(|len| len.checked_add(self.width.checked_mul(self.bpp / 8)?))
+                .unwrap_or(usize::MAX)
+        }
+    }
+
+    pub fn builder(format: PixelFormat, width: usize, height: usize) -> DitherBuilder {
+        DitherBuilder::new(format, width, height)
+    }
+
+    pub fn set_palette(&self, palette: &Palette) -> Result<()> {
+        let mut red = [0u32; 256];
+        let mut green = [0u32; 256];
+        let mut blue = [0u32; 256];
+        let mut alpha = [0u32; 256];
+
+        // libcaca wants 12 bits per channel
+        let channel = |value: u8| value as u32 * 0xfff / 0xff;
+
+        for (i, color) in palette.colors().iter().enumerate() {
+            red[i] = channel(color.r);
+            green[i] = channel(color.g);
+            blue[i] = channel(color.b);
+            alpha[i] = channel(color.a);
+        }
+
         if unsafe {
             caca_set_dither_palette(
                 self.as_internal(),
-                red.as_ptr() as *mut _,
-                green.as_ptr() as *mut _,
-                blue.as_ptr() as *mut _,
-                alpha.as_ptr() as *mut _,
+                red.as_mut_ptr(),
+                green.as_mut_ptr(),
+                blue.as_mut_ptr(),
+                alpha.as_mut_ptr(),
             )
         } != 0
         {
@@ -61,10 +148,63 @@ impl Dither {
                 what => Err(Error::Unknown(what)),
             }
         } else {
+            *self.palette.borrow_mut() = Some(palette.clone());
             Ok(())
         }
     }
 
+    // Reads back a pixel the way libcaca does, 8 bits dithers without a
+    // palette read as black.
+    pub(crate) fn pixel(&self, image: &[u8], x: usize, y: usize) -> Rgba {
+        let bytes = self.bpp / 8;
+        let offset = y * self.pitch + x * bytes;
+        let data = &image[offset..offset + bytes];
+
+        if bytes == 1 {
+            return match &*self.palette.borrow() {
+                Some(palette) => palette[data[0]],
+                None => Rgba::default(),
+            };
+        }
+
+        let value = match bytes {
+            2 => u16::from_ne_bytes([data[0], data[1]]) as u32,
+            3 if cfg!(target_endian = "little") => {
+                data[0] as u32 | (data[1] as u32) << 8 | (data[2] as u32) << 16
+            }
+            3 => (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32,
+            _ => u32::from_ne_bytes([data[0], data[1], data[2], data[3]]),
+        };
+
+        let channel = |mask: u32, missing: u8| {
+            if mask == 0 {
+                missing
+            } else {
+                let max = mask >> mask.trailing_zeros();
+                (((value & mask) >> mask.trailing_zeros()) * 0xff / max) as u8
+            }
+        };
+
+        Rgba::new(
+            channel(self.masks[0], 0),
+            channel(self.masks[1], 0),
+            channel(self.masks[2], 0),
+            channel(self.masks[3], 0xff),
+        )
+    }
+
+    // Every pixel, row after row.
+    pub(crate) fn pixels(&self, image: &[u8]) -> Vec<Rgba> {
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                pixels.push(self.pixel(image, x, y));
+            }
+        }
+
+        pixels
+    }
+
     pub fn set_brightness(&self, brightness: f32) -> Result<()> {
         if unsafe { caca_set_dither_brightness(self.as_internal(), brightness) } != 0 {
             match errno().0 {
@@ -111,9 +251,12 @@ impl Dither {
     }
 
     pub fn set_antialias(&self, antialias: Antialias) -> Result<()> {
-        let antialias = antialias.to_string();
-        let antialias = lossy_cstring(antialias);
-        if unsafe { caca_set_dither_antialias(self.as_internal(), antialias.as_ptr()) } != 0 {
+        self.set_antialias_name(antialias.to_string())
+    }
+
+    pub fn set_antialias_name<S: AsRef<str>>(&self, name: S) -> Result<()> {
+        let name = lossy_cstring(name);
+        if unsafe { caca_set_dither_antialias(self.as_internal(), name.as_ptr()) } != 0 {
             match errno().0 {
                 libc::EINVAL => Err(Error::InvalidAntialias),
                 what => Err(Error::Unknown(what)),
@@ -124,15 +267,25 @@ impl Dither {
     }
 
     pub fn antialias(&self) -> Result<Antialias> {
-        let s = unsafe { CStr::from_ptr(caca_get_dither_antialias(self.as_internal())) }
-            .to_string_lossy();
-        Antialias::from_str(s.as_ref())
+        Antialias::from_str(self.antialias_name().as_ref())
+    }
+
+    pub fn antialias_name(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(caca_get_dither_antialias(self.as_internal())) }.to_string_lossy()
+    }
+
+    // Names and descriptions of what the linked libcaca supports.
+    pub fn antialias_list(&self) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        unsafe { pairs_list(caca_get_dither_antialias_list(self.as_internal())) }
     }
 
     pub fn set_color(&self, color: DitherColor) -> Result<()> {
-        let color = color.to_string();
-        let color = lossy_cstring(color);
-        if unsafe { caca_set_dither_color(self.as_internal(), color.as_ptr()) } != 0 {
+        self.set_color_name(color.to_string())
+    }
+
+    pub fn set_color_name<S: AsRef<str>>(&self, name: S) -> Result<()> {
+        let name = lossy_cstring(name);
+        if unsafe { caca_set_dither_color(self.as_internal(), name.as_ptr()) } != 0 {
             match errno().0 {
                 libc::EINVAL => Err(Error::InvalidColor),
                 what => Err(Error::Unknown(what)),
@@ -143,15 +296,25 @@ impl Dither {
     }
 
     pub fn color(&self) -> Result<DitherColor> {
-        let s =
-            unsafe { CStr::from_ptr(caca_get_dither_color(self.as_internal())) }.to_string_lossy();
-        DitherColor::from_str(s.as_ref())
+        DitherColor::from_str(self.color_name().as_ref())
+    }
+
+    pub fn color_name(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(caca_get_dither_color(self.as_internal())) }.to_string_lossy()
+    }
+
+    // Names and descriptions of what the linked libcaca supports.
+    pub fn color_list(&self) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        unsafe { pairs_list(caca_get_dither_color_list(self.as_internal())) }
     }
 
     pub fn set_charset(&self, charset: DitherCharset) -> Result<()> {
-        let charset = charset.to_string();
-        let charset = lossy_cstring(charset);
-        if unsafe { caca_set_dither_charset(self.as_internal(), charset.as_ptr()) } != 0 {
+        self.set_charset_name(charset.to_string())
+    }
+
+    pub fn set_charset_name<S: AsRef<str>>(&self, name: S) -> Result<()> {
+        let name = lossy_cstring(name);
+        if unsafe { caca_set_dither_charset(self.as_internal(), name.as_ptr()) } != 0 {
             match errno().0 {
                 libc::EINVAL => Err(Error::InvalidCharset),
                 what => Err(Error::Unknown(what)),
@@ -162,15 +325,25 @@ impl Dither {
     }
 
     pub fn charset(&self) -> Result<DitherCharset> {
-        let s = unsafe { CStr::from_ptr(caca_get_dither_charset(self.as_internal())) }
-            .to_string_lossy();
-        DitherCharset::from_str(s.as_ref())
+        DitherCharset::from_str(self.charset_name().as_ref())
+    }
+
+    pub fn charset_name(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(caca_get_dither_charset(self.as_internal())) }.to_string_lossy()
+    }
+
+    // Names and descriptions of what the linked libcaca supports.
+    pub fn charset_list(&self) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        unsafe { pairs_list(caca_get_dither_charset_list(self.as_internal())) }
     }
 
     pub fn set_algorithm(&self, algorithm: DitherAlgorithm) -> Result<()> {
-        let algorithm = algorithm.to_string();
-        let algorithm = lossy_cstring(algorithm);
-        if unsafe { caca_set_dither_algorithm(self.as_internal(), algorithm.as_ptr()) } != 0 {
+        self.set_algorithm_name(algorithm.to_string())
+    }
+
+    pub fn set_algorithm_name<S: AsRef<str>>(&self, name: S) -> Result<()> {
+        let name = lossy_cstring(name);
+        if unsafe { caca_set_dither_algorithm(self.as_internal(), name.as_ptr()) } != 0 {
             match errno().0 {
                 libc::EINVAL => Err(Error::InvalidAlgorithm),
                 what => Err(Error::Unknown(what)),
@@ -181,13 +354,42 @@ impl Dither {
     }
 
     pub fn algorithm(&self) -> Result<DitherAlgorithm> {
-        let s = unsafe { CStr::from_ptr(caca_get_dither_algorithm(self.as_internal())) }
-            .to_string_lossy();
-        DitherAlgorithm::from_str(s.as_ref())
+        DitherAlgorithm::from_str(self.algorithm_name().as_ref())
+    }
+
+    pub fn algorithm_name(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(caca_get_dither_algorithm(self.as_internal())) }.to_string_lossy()
+    }
+
+    // Names and descriptions of what the linked libcaca supports.
+    pub fn algorithm_list(&self) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+        unsafe { pairs_list(caca_get_dither_algorithm_list(self.as_internal())) }
+    }
+
+    // Gamma suggested from the bitmap's luminance.
+    pub fn suggest_adjustments(&self, image: &[u8]) -> Result<Adjustments> {
+        if image.len() < self.buffer_len() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        Ok(Histogram::from_bitmap(self, image).suggest())
+    }
+
+    pub fn settings(&self) -> Result<DitherSettings> {
+        DitherSettings::from_dither(self)
+    }
+
+    // A 32 bits RGBA dither with the same settings, for bitmaps reworked on
+    // the Rust side.
+    pub(crate) fn rgba32_like(&self, width: usize, height: usize) -> Result<Dither> {
+        let dither = Dither::builder(PixelFormat::Rgba32, width, height).build()?;
+        self.settings()?.apply(&dither)?;
+
+        Ok(dither)
     }
 
     pub(crate) fn as_internal(&self) -> *mut caca_dither_t {
-        self.0
+        self.internal
     }
 }
 
@@ -197,6 +399,206 @@ impl Drop for Dither {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgb24,
+    Bgr24,
+    Rgba32,
+    Bgra32,
+    Argb32,
+    Rgb565,
+    Rgb555,
+    Indexed8,
+    Gray8,
+}
+
+// libcaca reads 24 and 32 bits pixels as native endian integers, byte
+// ordered formats need their masks computed accordingly.
+fn byte_mask(bytes: usize, index: usize) -> u32 {
+    if cfg!(target_endian = "little") {
+        0xff << (8 * index)
+    } else {
+        0xff << (8 * (bytes - 1 - index))
+    }
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::Rgb24 | Self::Bgr24 => 3,
+            Self::Rgba32 | Self::Bgra32 | Self::Argb32 => 4,
+            Self::Rgb565 | Self::Rgb555 => 2,
+            Self::Indexed8 | Self::Gray8 => 1,
+        }
+    }
+
+    pub fn bpp(&self) -> usize {
+        self.bytes_per_pixel() * 8
+    }
+
+    // Red, green, blue and alpha masks.
+    pub fn masks(&self) -> (u32, u32, u32, u32) {
+        let bytes = self.bytes_per_pixel();
+        let mask = |index| byte_mask(bytes, index);
+
+        match self {
+            Self::Rgb24 => (mask(0), mask(1), mask(2), 0),
+            Self::Bgr24 => (mask(2), mask(1), mask(0), 0),
+            Self::Rgba32 => (mask(0), mask(1), mask(2), mask(3)),
+            Self::Bgra32 => (mask(2), mask(1), mask(0), mask(3)),
+            Self::Argb32 => (mask(1), mask(2), mask(3), mask(0)),
+            Self::Rgb565 => (0xf800, 0x07e0, 0x001f, 0),
+            Self::Rgb555 => (0x7c00, 0x03e0, 0x001f, 0),
+            Self::Indexed8 | Self::Gray8 => (0, 0, 0, 0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DitherBuilder {
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+    pitch: Option<usize>,
+}
+
+impl DitherBuilder {
+    pub fn new(format: PixelFormat, width: usize, height: usize) -> DitherBuilder {
+        DitherBuilder {
+            format,
+            width,
+            height,
+            pitch: None,
+        }
+    }
+
+    // Defaults to tightly packed rows.
+    pub fn pitch(mut self, pitch: usize) -> DitherBuilder {
+        self.pitch = Some(pitch);
+        self
+    }
+
+    pub fn build(self) -> Result<Dither> {
+        if self.width == 0 || self.width > i32::MAX as usize {
+            return Err(Error::InvalidDitherParam("width"));
+        }
+        if self.height == 0 || self.height > i32::MAX as usize {
+            return Err(Error::InvalidDitherParam("height"));
+        }
+
+        let row = self
+            .width
+            .checked_mul(self.format.bytes_per_pixel())
+            .ok_or(Error::InvalidSize)?;
+        let pitch = self.pitch.unwrap_or(row);
+        if pitch < row || pitch > i32::MAX as usize {
+            return Err(Error::InvalidDitherParam("pitch"));
+        }
+        // Whole bitmap, the last row without its padding
+        if pitch
+            .checked_mul(self.height - 1)
+            .and_then(|len| len.checked_add(row))
+            .is_none()
+        {
+            return Err(Error::InvalidSize);
+        }
+
+        let (rmask, gmask, bmask, amask) = self.format.masks();
+        let dither = Dither::new(
+            self.format.bpp() as i32,
+            self.width as i32,
+            self.height as i32,
+            pitch as i32,
+            rmask,
+            gmask,
+            bmask,
+            amask,
+        )?;
+
+        if self.format == PixelFormat::Gray8 {
+            dither.set_palette(&Palette::grayscale())?;
+        }
+
+        Ok(dither)
+    }
+}
+
+// The 256 colours used by 8 bits dithers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette([Rgba; 256]);
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette([Rgba::default(); 256])
+    }
+}
+
+impl Palette {
+    pub fn new() -> Palette {
+        Self::default()
+    }
+
+    // Missing entries are left black.
+    pub fn from_rgb(colors: &[(u8, u8, u8)]) -> Result<Palette> {
+        if colors.len() > 256 {
+            return Err(Error::InvalidSize);
+        }
+
+        let mut palette = Self::default();
+        for (entry, &(r, g, b)) in palette.0.iter_mut().zip(colors) {
+            *entry = Rgba::rgb(r, g, b);
+        }
+
+        Ok(palette)
+    }
+
+    // Packed RGB triples, like GIF colour tables and PCX palettes (without
+    // the 0x0c marker).
+    pub fn from_bytes(data: &[u8]) -> Result<Palette> {
+        if !data.len().is_multiple_of(3) || data.len() > 256 * 3 {
+            return Err(Error::InvalidSize);
+        }
+
+        let colors: Vec<(u8, u8, u8)> = data
+            .chunks_exact(3)
+            .map(|rgb| (rgb[0], rgb[1], rgb[2]))
+            .collect();
+
+        Self::from_rgb(&colors)
+    }
+
+    pub fn grayscale() -> Palette {
+        let mut palette = Self::default();
+        for (i, entry) in palette.0.iter_mut().enumerate() {
+            *entry = Rgba::rgb(i as u8, i as u8, i as u8);
+        }
+
+        palette
+    }
+
+    pub fn colors(&self) -> &[Rgba; 256] {
+        &self.0
+    }
+
+    pub fn colors_mut(&mut self) -> &mut [Rgba; 256] {
+        &mut self.0
+    }
+}
+
+impl Index<u8> for Palette {
+    type Output = Rgba;
+
+    fn index(&self, index: u8) -> &Rgba {
+        &self.0[index as usize]
+    }
+}
+
+impl IndexMut<u8> for Palette {
+    fn index_mut(&mut self, index: u8) -> &mut Rgba {
+        &mut self.0[index as usize]
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Antialias {
     None,
@@ -358,3 +760,167 @@ impl FromStr for DitherAlgorithm {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BlueNoise, Diffusion, Dither, Palette, PixelFormat};
+    use crate::{error::Error, Boundaries, Canvas, Rectangle, Rgba};
+
+    #[test]
+    fn builder() {
+        for &format in &[
+            PixelFormat::Rgb24,
+            PixelFormat::Bgr24,
+            PixelFormat::Rgba32,
+            PixelFormat::Bgra32,
+            PixelFormat::Argb32,
+            PixelFormat::Rgb565,
+            PixelFormat::Rgb555,
+            PixelFormat::Indexed8,
+            PixelFormat::Gray8,
+        ] {
+            assert!(Dither::builder(format, 16, 8).build().is_ok());
+        }
+
+        assert!(matches!(
+            Dither::builder(PixelFormat::Rgb24, 0, 8).build(),
+            Err(Error::InvalidDitherParam("width"))
+        ));
+        assert!(matches!(
+            Dither::builder(PixelFormat::Rgb24, 16, 8).pitch(47).build(),
+            Err(Error::InvalidDitherParam("pitch"))
+        ));
+        let dither = Dither::builder(PixelFormat::Rgb24, 16, 8)
+            .pitch(64)
+            .build()
+            .unwrap();
+        assert_eq!(dither.pitch(), 64);
+        assert_eq!(dither.buffer_len(), 64 * 7 + 48);
+    }
+
+    #[test]
+    fn dither_bitmap() {
+        let dither = Dither::builder(PixelFormat::Rgba32, 4, 4).build().unwrap();
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 2,
+            height: 2,
+        })
+        .unwrap();
+        let rect = Rectangle {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 2,
+        };
+
+        let image = vec![0xffu8; dither.buffer_len()];
+        assert!(canvas.dither_bitmap(&rect, &dither, &image).is_ok());
+        assert!(matches!(
+            canvas.dither_bitmap(&rect, &dither, &image[1..]),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn pixel() {
+        let dither = Dither::builder(PixelFormat::Bgr24, 2, 1).build().unwrap();
+        let image = [0x10, 0x20, 0x30, 0xff, 0x00, 0x80];
+        assert_eq!(dither.pixel(&image, 0, 0), Rgba::rgb(0x30, 0x20, 0x10));
+        assert_eq!(dither.pixel(&image, 1, 0), Rgba::rgb(0x80, 0x00, 0xff));
+
+        let dither = Dither::builder(PixelFormat::Gray8, 1, 1).build().unwrap();
+        assert_eq!(dither.pixel(&[0x42], 0, 0), Rgba::rgb(0x42, 0x42, 0x42));
+    }
+
+    #[test]
+    fn dither_bitmap_with() {
+        let dither = Dither::builder(PixelFormat::Gray8, 32, 8).build().unwrap();
+        let image: Vec<u8> = (0..dither.buffer_len())
+            .map(|i| (i % 32 * 8) as u8)
+            .collect();
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 16,
+            height: 4,
+        })
+        .unwrap();
+        let rect = Rectangle {
+            x: 0,
+            y: 0,
+            width: 16,
+            height: 4,
+        };
+
+        assert!(canvas
+            .dither_bitmap_with(&rect, &dither, &image, &Diffusion::Stucki)
+            .is_ok());
+        assert!(canvas.chars().iter().any(|&ch| ch != ' ' as u32));
+        assert!(canvas
+            .dither_bitmap_with(&rect, &dither, &image, &BlueNoise)
+            .is_ok());
+        assert!(matches!(
+            canvas.dither_bitmap_with(&rect, &dither, &image[1..], &Diffusion::Atkinson),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn palette() {
+        let gray = Palette::grayscale();
+        assert_eq!(gray[0], Rgba::rgb(0, 0, 0));
+        assert_eq!(gray[128], Rgba::rgb(128, 128, 128));
+        assert_eq!(gray[255], Rgba::rgb(255, 255, 255));
+
+        let palette = Palette::from_bytes(&[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(palette[1], Rgba::rgb(4, 5, 6));
+        assert_eq!(palette[2], Rgba::default());
+
+        assert!(Palette::from_bytes(&[1, 2]).is_err());
+        assert!(Palette::from_bytes(&[0; 769]).is_err());
+        assert!(Palette::from_rgb(&[(0, 0, 0); 257]).is_err());
+
+        let dither = Dither::builder(PixelFormat::Indexed8, 4, 4)
+            .build()
+            .unwrap();
+        assert!(dither.set_palette(&palette).is_ok());
+    }
+
+    #[test]
+    fn lists() {
+        let dither = Dither::builder(PixelFormat::Rgb24, 4, 4).build().unwrap();
+
+        for (name, _) in dither.algorithm_list() {
+            dither.set_algorithm_name(&name).unwrap();
+            assert_eq!(dither.algorithm_name(), name);
+        }
+        for (name, _) in dither.charset_list() {
+            dither.set_charset_name(&name).unwrap();
+        }
+        for (name, _) in dither.color_list() {
+            dither.set_color_name(&name).unwrap();
+        }
+        for (name, _) in dither.antialias_list() {
+            dither.set_antialias_name(&name).unwrap();
+        }
+
+        assert!(dither
+            .algorithm_list()
+            .iter()
+            .any(|(name, _)| name == "fstein"));
+        assert!(matches!(
+            dither.set_algorithm_name("nope"),
+            Err(Error::InvalidAlgorithm)
+        ));
+    }
+
+    #[test]
+    fn masks() {
+        let pixel = [0x11u8, 0x22, 0x33, 0x44];
+        let value = u32::from_ne_bytes(pixel);
+        let (r, g, b, a) = PixelFormat::Argb32.masks();
+
+        assert_eq!(value & a, u32::from_ne_bytes([0x11, 0, 0, 0]));
+        assert_eq!(value & r, u32::from_ne_bytes([0, 0x22, 0, 0]));
+        assert_eq!(value & g, u32::from_ne_bytes([0, 0, 0x33, 0]));
+        assert_eq!(value & b, u32::from_ne_bytes([0, 0, 0, 0x44]));
+    }
+}
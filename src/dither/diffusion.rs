@@ -0,0 +1,569 @@
+use std::convert::TryFrom;
+
+use crate::{
+    attr::Attr,
+    canvas::Canvas,
+    color::Rgba,
+    dither::{Dither, DitherCharset},
+    error::Error,
+    result::Result,
+    Color, Point, Rectangle,
+};
+
+// A quantization pass run on the Rust side, one colour per canvas cell.
+// `pixels` holds `width * height` RGB values in 0.0..=1.0, row after row, and
+// every one of them must be replaced by an entry of `palette`.
+pub trait DitherStage {
+    fn apply(&self, pixels: &mut [[f32; 3]], width: usize, height: usize, palette: &[[f32; 3]]);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Diffusion {
+    Atkinson,
+    JarvisJudiceNinke,
+    Stucki,
+    Sierra,
+    SierraTwoRow,
+    SierraLite,
+    Burkes,
+}
+
+impl Diffusion {
+    // Neighbours as (dx, dy, weight), and the divisor of the weights.
+    fn kernel(&self) -> (&'static [(isize, usize, f32)], f32) {
+        match self {
+            // Only spreads 6/8 of the error, on purpose.
+            Self::Atkinson => (
+                &[
+                    (1, 0, 1.0),
+                    (2, 0, 1.0),
+                    (-1, 1, 1.0),
+                    (0, 1, 1.0),
+                    (1, 1, 1.0),
+                    (0, 2, 1.0),
+                ],
+                8.0,
+            ),
+            Self::JarvisJudiceNinke => (
+                &[
+                    (1, 0, 7.0),
+                    (2, 0, 5.0),
+                    (-2, 1, 3.0),
+                    (-1, 1, 5.0),
+                    (0, 1, 7.0),
+                    (1, 1, 5.0),
+                    (2, 1, 3.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 3.0),
+                    (0, 2, 5.0),
+                    (1, 2, 3.0),
+                    (2, 2, 1.0),
+                ],
+                48.0,
+            ),
+            Self::Stucki => (
+                &[
+                    (1, 0, 8.0),
+                    (2, 0, 4.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 8.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 4.0),
+                    (1, 2, 2.0),
+                    (2, 2, 1.0),
+                ],
+                42.0,
+            ),
+            Self::Sierra => (
+                &[
+                    (1, 0, 5.0),
+                    (2, 0, 3.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 5.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 3.0),
+                    (1, 2, 2.0),
+                ],
+                32.0,
+            ),
+            Self::SierraTwoRow => (
+                &[
+                    (1, 0, 4.0),
+                    (2, 0, 3.0),
+                    (-2, 1, 1.0),
+                    (-1, 1, 2.0),
+                    (0, 1, 3.0),
+                    (1, 1, 2.0),
+                    (2, 1, 1.0),
+                ],
+                16.0,
+            ),
+            Self::SierraLite => (&[(1, 0, 2.0), (-1, 1, 1.0), (0, 1, 1.0)], 4.0),
+            Self::Burkes => (
+                &[
+                    (1, 0, 8.0),
+                    (2, 0, 4.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 8.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                ],
+                32.0,
+            ),
+        }
+    }
+}
+
+impl DitherStage for Diffusion {
+    fn apply(&self, pixels: &mut [[f32; 3]], width: usize, height: usize, palette: &[[f32; 3]]) {
+        let (kernel, divisor) = self.kernel();
+
+        for y in 0..height {
+            for x in 0..width {
+                let old = pixels[y * width + x];
+                let new = nearest(palette, old);
+                pixels[y * width + x] = new;
+
+                for &(dx, dy, weight) in kernel {
+                    let nx = x as isize + dx;
+                    let ny = y + dy;
+                    if nx < 0 || nx as usize >= width || ny >= height {
+                        continue;
+                    }
+
+                    let target = &mut pixels[ny * width + nx as usize];
+                    for c in 0..3 {
+                        target[c] += (old[c] - new[c]) * weight / divisor;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Ordered dithering against a void-and-cluster threshold map, no visible
+// crosshatch like libcaca's Bayer matrices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct BlueNoise;
+
+const NOISE_SIZE: usize = 16;
+
+impl DitherStage for BlueNoise {
+    fn apply(&self, pixels: &mut [[f32; 3]], width: usize, height: usize, palette: &[[f32; 3]]) {
+        let map = blue_noise(NOISE_SIZE);
+        let spread = spread(palette);
+
+        for y in 0..height {
+            for x in 0..width {
+                let rank = map[(y % NOISE_SIZE) * NOISE_SIZE + x % NOISE_SIZE];
+                let offset = ((rank as f32 + 0.5) / map.len() as f32 - 0.5) * spread;
+
+                let pixel = &mut pixels[y * width + x];
+                *pixel = nearest(
+                    palette,
+                    [pixel[0] + offset, pixel[1] + offset, pixel[2] + offset],
+                );
+            }
+        }
+    }
+}
+
+fn nearest(palette: &[[f32; 3]], color: [f32; 3]) -> [f32; 3] {
+    nearest_index(palette, color)
+        .map(|i| palette[i])
+        .unwrap_or(color)
+}
+
+fn nearest_index(palette: &[[f32; 3]], color: [f32; 3]) -> Option<usize> {
+    let distance =
+        |entry: &[f32; 3]| -> f32 { (0..3).map(|c| (entry[c] - color[c]).powi(2)).sum() };
+
+    (0..palette.len()).min_by(|&a, &b| distance(&palette[a]).total_cmp(&distance(&palette[b])))
+}
+
+// Average gap between palette entries, per channel; how far the threshold
+// map has to push a colour to reach a neighbouring entry.
+fn spread(palette: &[[f32; 3]]) -> f32 {
+    let gaps: Vec<f32> = palette
+        .iter()
+        .map(|a| {
+            palette
+                .iter()
+                .map(|b| (0..3).map(|c| (a[c] - b[c]).powi(2)).sum::<f32>())
+                .filter(|&d| d > 0.0)
+                .fold(f32::INFINITY, f32::min)
+        })
+        .filter(|d| d.is_finite())
+        .map(|d| (d / 3.0).sqrt())
+        .collect();
+
+    if gaps.is_empty() {
+        0.0
+    } else {
+        gaps.iter().sum::<f32>() / gaps.len() as f32
+    }
+}
+
+// Ulichney's void-and-cluster, returns the rank of every cell of a toroidal
+// `size * size` map.
+fn blue_noise(size: usize) -> Vec<usize> {
+    let len = size * size;
+    let sigma = 1.5f32;
+
+    let mut gaussian = vec![0f32; len];
+    for y in 0..size {
+        for x in 0..size {
+            let dx = x.min(size - x) as f32;
+            let dy = y.min(size - y) as f32;
+            gaussian[y * size + x] = (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp();
+        }
+    }
+
+    let update = |energy: &mut [f32], at: usize, sign: f32| {
+        let (ax, ay) = (at % size, at / size);
+        for y in 0..size {
+            for x in 0..size {
+                let dx = (x + size - ax) % size;
+                let dy = (y + size - ay) % size;
+                energy[y * size + x] += sign * gaussian[dy * size + dx];
+            }
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f32]| {
+        (0..len)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..len)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+
+    // Deterministic initial pattern, about a tenth of the cells set
+    let mut pattern = vec![false; len];
+    let mut energy = vec![0f32; len];
+    let mut seed = 0x2545_f491u32;
+    let mut ones = 0;
+    while ones < len / 10 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let at = (seed >> 8) as usize % len;
+        if !pattern[at] {
+            pattern[at] = true;
+            update(&mut energy, at, 1.0);
+            ones += 1;
+        }
+    }
+
+    // Spread it out until moving the tightest cluster doesn't help anymore
+    for _ in 0..len {
+        let cluster = match tightest_cluster(&pattern, &energy) {
+            Some(cluster) => cluster,
+            None => break,
+        };
+        pattern[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+
+        let void = largest_void(&pattern, &energy).unwrap_or(cluster);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; len];
+
+    let mut shrinking = pattern.clone();
+    let mut shrinking_energy = energy.clone();
+    for r in (0..ones).rev() {
+        if let Some(cluster) = tightest_cluster(&shrinking, &shrinking_energy) {
+            shrinking[cluster] = false;
+            update(&mut shrinking_energy, cluster, -1.0);
+            rank[cluster] = r;
+        }
+    }
+
+    for r in ones..len {
+        if let Some(void) = largest_void(&pattern, &energy) {
+            pattern[void] = true;
+            update(&mut energy, void, 1.0);
+            rank[void] = r;
+        }
+    }
+
+    rank
+}
+
+// The ANSI colours cells get with the given dither colour mode, along with
+// how libcaca draws them.
+fn palette_for(color: &str) -> Vec<(Color, [f32; 3])> {
+    let colors: &[Color] = match color {
+        "mono" => &[Color::Black, Color::White],
+        "gray" | "fullgray" => &[
+            Color::Black,
+            Color::DarkGray,
+            Color::LightGray,
+            Color::White,
+        ],
+        "8" | "full8" => &[
+            Color::Black,
+            Color::Blue,
+            Color::Green,
+            Color::Cyan,
+            Color::Red,
+            Color::Magenta,
+            Color::Brown,
+            Color::LightGray,
+        ],
+        _ => &[
+            Color::Black,
+            Color::Blue,
+            Color::Green,
+            Color::Cyan,
+            Color::Red,
+            Color::Magenta,
+            Color::Brown,
+            Color::LightGray,
+            Color::DarkGray,
+            Color::LightBlue,
+            Color::LightGreen,
+            Color::LightCyan,
+            Color::LightRed,
+            Color::LightMagenta,
+            Color::Yellow,
+            Color::White,
+        ],
+    };
+
+    colors
+        .iter()
+        .filter_map(|&color| Rgba::try_from(color).ok().map(|rgba| (color, rgba)))
+        .map(|(color, rgba)| {
+            (
+                color,
+                [
+                    rgba.r as f32 / 255.0,
+                    rgba.g as f32 / 255.0,
+                    rgba.b as f32 / 255.0,
+                ],
+            )
+        })
+        .collect()
+}
+
+// Box filters the bitmap down to one sample per cell of the target area.
+pub(crate) fn resample(
+    dither: &Dither,
+    image: &[u8],
+    width: usize,
+    height: usize,
+) -> Vec<[f32; 4]> {
+    let mut cells = Vec::with_capacity(width * height);
+
+    for cy in 0..height {
+        let y0 = cy * dither.height() / height;
+        let y1 = ((cy + 1) * dither.height() / height).max(y0 + 1);
+
+        for cx in 0..width {
+            let x0 = cx * dither.width() / width;
+            let x1 = ((cx + 1) * dither.width() / width).max(x0 + 1);
+
+            let mut sum = [0f32; 4];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let pixel = dither.pixel(image, x, y);
+                    sum[0] += pixel.r as f32;
+                    sum[1] += pixel.g as f32;
+                    sum[2] += pixel.b as f32;
+                    sum[3] += pixel.a as f32;
+                }
+            }
+
+            let count = ((y1 - y0) * (x1 - x0)) as f32 * 255.0;
+            cells.push([
+                sum[0] / count,
+                sum[1] / count,
+                sum[2] / count,
+                sum[3] / count,
+            ]);
+        }
+    }
+
+    cells
+}
+
+// Same contract as `Canvas::dither_bitmap`, but colours are chosen by `stage`
+// and written as one solid cell each, libcaca would dither them again
+// against its own palette otherwise.
+pub(crate) fn dither_bitmap<S: DitherStage + ?Sized>(
+    canvas: &mut Canvas,
+    rect: &Rectangle,
+    dither: &Dither,
+    image: &[u8],
+    stage: &S,
+) -> Result<()> {
+    if image.len() < dither.buffer_len() {
+        return Err(Error::BufferTooSmall);
+    }
+    if rect.width == 0 || rect.height == 0 {
+        return Ok(());
+    }
+
+    let (width, height) = (rect.width, rect.height);
+    let cells = resample(dither, image, width, height);
+
+    // libcaca's gamma curve, applied here so the stage quantizes what
+    // libcaca would have seen
+    let gamma = 1.0 / dither.gamma();
+    let mut colors: Vec<[f32; 3]> = cells
+        .iter()
+        .map(|c| [c[0].powf(gamma), c[1].powf(gamma), c[2].powf(gamma)])
+        .collect();
+    let (ansi, palette): (Vec<Color>, Vec<[f32; 3]>) =
+        palette_for(&dither.color_name()).into_iter().unzip();
+    stage.apply(&mut colors, width, height, &palette);
+
+    let glyph = match dither.charset()? {
+        DitherCharset::Ascii => '#',
+        DitherCharset::Shades | DitherCharset::Blocks => '█',
+    };
+    // (-1, -1) reads the current attribute
+    let previous = canvas.get_attr(&Point { x: -1, y: -1 });
+
+    for cy in 0..height {
+        let y = rect.y + cy as i32;
+        if y < 0 || y as usize >= canvas.height() {
+            continue;
+        }
+
+        for cx in 0..width {
+            let x = rect.x + cx as i32;
+            // Mostly transparent cells are left alone
+            if x < 0 || x as usize >= canvas.width() || cells[cy * width + cx][3] < 0.5 {
+                continue;
+            }
+
+            let color = match nearest_index(&palette, colors[cy * width + cx]) {
+                Some(i) => ansi[i],
+                None => continue,
+            };
+            canvas.set_attr(Attr::new(color, color));
+            canvas.put_char(&Point { x, y }, glyph as u32);
+        }
+    }
+
+    canvas.set_attr(previous);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blue_noise, palette_for, BlueNoise, Diffusion, DitherStage};
+    use crate::{
+        dither::{DitherCharset, PixelFormat},
+        Boundaries, Canvas, Color, Dither, Point, Rectangle,
+    };
+
+    fn rgb(color: &str) -> Vec<[f32; 3]> {
+        palette_for(color).into_iter().map(|(_, rgb)| rgb).collect()
+    }
+
+    #[test]
+    fn blue_noise_ranks() {
+        let mut ranks = blue_noise(16);
+        ranks.sort_unstable();
+        assert_eq!(ranks, (0..256).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn quantizes_to_palette() {
+        let palette = rgb("mono");
+        let stages: Vec<Box<dyn DitherStage>> = vec![
+            Box::new(Diffusion::Atkinson),
+            Box::new(Diffusion::JarvisJudiceNinke),
+            Box::new(Diffusion::Stucki),
+            Box::new(Diffusion::Sierra),
+            Box::new(Diffusion::SierraTwoRow),
+            Box::new(Diffusion::SierraLite),
+            Box::new(Diffusion::Burkes),
+            Box::new(BlueNoise),
+        ];
+
+        for stage in stages {
+            let mut pixels = vec![[0.5f32; 3]; 32 * 32];
+            stage.apply(&mut pixels, 32, 32, &palette);
+
+            assert!(pixels.iter().all(|p| palette.contains(p)));
+
+            // Mid gray comes out as a mix of both
+            let white = pixels.iter().filter(|p| p[0] > 0.5).count();
+            assert!(white > 32 * 32 / 4 && white < 32 * 32 * 3 / 4);
+        }
+
+        // NaN colours still come out on the palette
+        let mut pixels = vec![[f32::NAN; 3]; 4];
+        Diffusion::Atkinson.apply(&mut pixels, 2, 2, &palette);
+        BlueNoise.apply(&mut pixels, 2, 2, &palette);
+        assert!(pixels.iter().all(|p| palette.contains(p)));
+    }
+
+    #[test]
+    fn ansi_palette() {
+        let palette = rgb("8");
+        assert_eq!(palette.len(), 8);
+        // Brown and light gray as libcaca draws them
+        assert!(palette.contains(&[2.0 / 3.0, 1.0 / 3.0, 0.0]));
+        assert!(palette.contains(&[2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0]));
+
+        assert!(rgb("16").contains(&[1.0 / 3.0, 1.0 / 3.0, 1.0]));
+    }
+
+    #[test]
+    fn solid_cells() {
+        // Plain ANSI blue, two pixels per cell each way
+        let image = [0u8, 0, 0xaa].repeat(8 * 4);
+        let dither = Dither::builder(PixelFormat::Rgb24, 8, 4).build().unwrap();
+        dither.set_charset(DitherCharset::Blocks).unwrap();
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 6,
+            height: 2,
+        })
+        .unwrap();
+
+        canvas
+            .dither_bitmap_with(
+                &Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 2,
+                },
+                &dither,
+                &image,
+                &Diffusion::Atkinson,
+            )
+            .unwrap();
+
+        for y in 0..2 {
+            for x in 0..4 {
+                let point = Point { x, y };
+                let attr = canvas.get_attr(&point);
+                assert_eq!(canvas.get_char(&point), '█' as u32);
+                assert_eq!(attr.ansi_fg().unwrap(), Color::Blue);
+                assert_eq!(attr.ansi_bg().unwrap(), Color::Blue);
+            }
+        }
+        assert_eq!(canvas.get_char(&Point { x: 5, y: 0 }), ' ' as u32);
+    }
+}
@@ -0,0 +1,474 @@
+use std::f32::consts::PI;
+
+use crate::{
+    attr::Attr, canvas::Canvas, dither::Dither, error::Error, result::Result, Color, Point,
+    Rectangle,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EdgeDetector {
+    // Thresholded gradient, thick edges
+    Sobel,
+    // Thin edges, blurred first then traced with hysteresis
+    Canny,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EdgeGlyphs {
+    // | / - \ _
+    Ascii,
+    // Light box drawing lines, with corners and junctions
+    BoxDrawing,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineArt {
+    detector: EdgeDetector,
+    glyphs: EdgeGlyphs,
+    threshold: f32,
+    coverage: f32,
+    color: Color,
+    fill: bool,
+}
+
+impl Default for LineArt {
+    fn default() -> Self {
+        LineArt {
+            detector: EdgeDetector::Sobel,
+            glyphs: EdgeGlyphs::Ascii,
+            threshold: 0.25,
+            coverage: 0.5,
+            color: Color::White,
+            fill: true,
+        }
+    }
+}
+
+impl LineArt {
+    pub fn new(detector: EdgeDetector) -> LineArt {
+        LineArt {
+            detector,
+            ..Self::default()
+        }
+    }
+
+    pub fn glyphs(mut self, glyphs: EdgeGlyphs) -> LineArt {
+        self.glyphs = glyphs;
+        self
+    }
+
+    // Gradient strength, 0.0..=1.0, for a pixel to be part of an edge. Canny
+    // keeps weaker pixels down to half of it when they touch a strong one.
+    pub fn threshold(mut self, threshold: f32) -> LineArt {
+        self.threshold = threshold;
+        self
+    }
+
+    // How many edge pixels a cell needs, as a fraction of its shortest side.
+    pub fn coverage(mut self, coverage: f32) -> LineArt {
+        self.coverage = coverage;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> LineArt {
+        self.color = color;
+        self
+    }
+
+    // Whether the regular dithered fill goes under the edges.
+    pub fn fill(mut self, fill: bool) -> LineArt {
+        self.fill = fill;
+        self
+    }
+}
+
+// Horizontal and vertical gradients, from a 3x3 Sobel operator with clamped
+// borders, scaled so a black to white step is 1.0.
+fn sobel(luma: &[f32], width: usize, height: usize) -> (Vec<f32>, Vec<f32>) {
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        luma[y * width + x]
+    };
+
+    let mut gx = vec![0f32; luma.len()];
+    let mut gy = vec![0f32; luma.len()];
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let i = y as usize * width + x as usize;
+            gx[i] = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1))
+                / 4.0;
+            gy[i] = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1))
+                / 4.0;
+        }
+    }
+
+    (gx, gy)
+}
+
+fn blur(luma: &[f32], width: usize, height: usize) -> Vec<f32> {
+    const KERNEL: [f32; 5] = [1.0, 4.0, 6.0, 4.0, 1.0];
+
+    let pass = |src: &[f32], dx: isize, dy: isize| {
+        let mut dst = vec![0f32; src.len()];
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let mut sum = 0.0;
+                for (k, weight) in KERNEL.iter().enumerate() {
+                    let offset = k as isize - 2;
+                    let sx = (x + offset * dx).clamp(0, width as isize - 1) as usize;
+                    let sy = (y + offset * dy).clamp(0, height as isize - 1) as usize;
+                    sum += weight * src[sy * width + sx];
+                }
+                dst[y as usize * width + x as usize] = sum / 16.0;
+            }
+        }
+        dst
+    };
+
+    pass(&pass(luma, 1, 0), 0, 1)
+}
+
+// Which of the four 45° sectors, centered on 0°, 45°, 90° and 135°, a
+// gradient angle falls in.
+fn sector(angle: f32) -> usize {
+    ((angle.rem_euclid(PI) / (PI / 4.0)).round() as usize) % 4
+}
+
+// Edge pixels and their gradients.
+fn detect(
+    detector: EdgeDetector,
+    threshold: f32,
+    luma: &[f32],
+    width: usize,
+    height: usize,
+) -> (Vec<bool>, Vec<f32>, Vec<f32>) {
+    match detector {
+        EdgeDetector::Sobel => {
+            let (gx, gy) = sobel(luma, width, height);
+            let edges = gx
+                .iter()
+                .zip(&gy)
+                .map(|(x, y)| x.hypot(*y) >= threshold)
+                .collect();
+            (edges, gx, gy)
+        }
+        EdgeDetector::Canny => {
+            let (gx, gy) = sobel(&blur(luma, width, height), width, height);
+            let magnitude: Vec<f32> = gx.iter().zip(&gy).map(|(x, y)| x.hypot(*y)).collect();
+
+            // Non-maximum suppression along the gradient
+            let mut thin = vec![0f32; magnitude.len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let i = y * width + x;
+                    let (dx, dy) = [(1, 0), (1, 1), (0, 1), (-1, 1)][sector(gy[i].atan2(gx[i]))];
+
+                    let neighbour = |sign: isize| {
+                        let nx = x as isize + sign * dx;
+                        let ny = y as isize + sign * dy;
+                        if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                            0.0
+                        } else {
+                            magnitude[ny as usize * width + nx as usize]
+                        }
+                    };
+
+                    if magnitude[i] >= neighbour(1) && magnitude[i] >= neighbour(-1) {
+                        thin[i] = magnitude[i];
+                    }
+                }
+            }
+
+            // Hysteresis, weak pixels survive when connected to strong ones
+            let low = threshold / 2.0;
+            let mut edges = vec![false; thin.len()];
+            let mut stack: Vec<usize> = (0..thin.len()).filter(|&i| thin[i] >= threshold).collect();
+            for &i in &stack {
+                edges[i] = true;
+            }
+            while let Some(i) = stack.pop() {
+                let (x, y) = ((i % width) as isize, (i / width) as isize);
+                for ny in y - 1..=y + 1 {
+                    for nx in x - 1..=x + 1 {
+                        if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                            continue;
+                        }
+                        let n = ny as usize * width + nx as usize;
+                        if !edges[n] && thin[n] >= low {
+                            edges[n] = true;
+                            stack.push(n);
+                        }
+                    }
+                }
+            }
+
+            (edges, gx, gy)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Direction {
+    Vertical,
+    Rising,
+    Horizontal,
+    Falling,
+}
+
+// Edge direction of every cell of the `columns * rows` grid, `low` is set
+// for horizontal edges sitting in the bottom third of their cell.
+fn edge_cells(
+    settings: &LineArt,
+    luma: &[f32],
+    width: usize,
+    height: usize,
+    columns: usize,
+    rows: usize,
+) -> Vec<Option<(Direction, bool)>> {
+    let (edges, gx, gy) = detect(settings.detector, settings.threshold, luma, width, height);
+    let mut cells = Vec::with_capacity(columns * rows);
+
+    for cy in 0..rows {
+        let y0 = cy * height / rows;
+        let y1 = ((cy + 1) * height / rows).max(y0 + 1).min(height);
+
+        for cx in 0..columns {
+            let x0 = cx * width / columns;
+            let x1 = ((cx + 1) * width / columns).max(x0 + 1).min(width);
+
+            // Gradient angles are averaged doubled, so opposite gradients
+            // along the same edge add up instead of cancelling out
+            let (mut count, mut cos, mut sin, mut rows_sum) = (0usize, 0f32, 0f32, 0f32);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let i = y * width + x;
+                    if edges[i] {
+                        let angle = gy[i].atan2(gx[i]);
+                        let weight = gx[i].hypot(gy[i]);
+                        count += 1;
+                        cos += weight * (2.0 * angle).cos();
+                        sin += weight * (2.0 * angle).sin();
+                        rows_sum += (y - y0) as f32 + 0.5;
+                    }
+                }
+            }
+
+            let needed = (settings.coverage * (x1 - x0).min(y1 - y0) as f32).max(1.0);
+            if (count as f32) < needed {
+                cells.push(None);
+                continue;
+            }
+
+            // Edges run across their gradient
+            let direction = [
+                Direction::Vertical,
+                Direction::Rising,
+                Direction::Horizontal,
+                Direction::Falling,
+            ][sector(sin.atan2(cos) / 2.0)];
+            let low = rows_sum / count as f32 > (y1 - y0) as f32 * 2.0 / 3.0;
+
+            cells.push(Some((direction, low)));
+        }
+    }
+
+    cells
+}
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+fn glyphs(
+    settings: &LineArt,
+    cells: &[Option<(Direction, bool)>],
+    columns: usize,
+) -> Vec<Option<char>> {
+    let at = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x as usize >= columns {
+            None
+        } else {
+            cells
+                .get(y as usize * columns + x as usize)
+                .copied()
+                .flatten()
+        }
+    };
+
+    cells
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| {
+            let (direction, low) = (*cell)?;
+            if settings.glyphs == EdgeGlyphs::Ascii {
+                return Some(match direction {
+                    Direction::Vertical => '|',
+                    Direction::Rising => '/',
+                    Direction::Horizontal if low => '_',
+                    Direction::Horizontal => '-',
+                    Direction::Falling => '\\',
+                });
+            }
+
+            let (x, y) = ((i % columns) as isize, (i / columns) as isize);
+            let joins = |other: Option<(Direction, bool)>, along: Direction| match other {
+                Some((d, _)) => d == along,
+                None => false,
+            };
+
+            let mut links = 0;
+            if joins(at(x, y - 1), Direction::Vertical) {
+                links |= UP;
+            }
+            if joins(at(x, y + 1), Direction::Vertical) {
+                links |= DOWN;
+            }
+            if joins(at(x - 1, y), Direction::Horizontal) {
+                links |= LEFT;
+            }
+            if joins(at(x + 1, y), Direction::Horizontal) {
+                links |= RIGHT;
+            }
+
+            Some(match (direction, links) {
+                (Direction::Rising, _) => '╱',
+                (Direction::Falling, _) => '╲',
+                (_, l) if l == RIGHT | DOWN => '┌',
+                (_, l) if l == LEFT | DOWN => '┐',
+                (_, l) if l == RIGHT | UP => '└',
+                (_, l) if l == LEFT | UP => '┘',
+                (_, l) if l == LEFT | RIGHT | DOWN => '┬',
+                (_, l) if l == LEFT | RIGHT | UP => '┴',
+                (_, l) if l == UP | DOWN | RIGHT => '├',
+                (_, l) if l == UP | DOWN | LEFT => '┤',
+                (_, l) if l == UP | DOWN | LEFT | RIGHT => '┼',
+                (Direction::Vertical, _) => '│',
+                (Direction::Horizontal, _) => '─',
+            })
+        })
+        .collect()
+}
+
+// The regular fill first, then the edges drawn over it in the settings'
+// colour, keeping the fill's background.
+pub(crate) fn dither_bitmap(
+    canvas: &mut Canvas,
+    rect: &Rectangle,
+    dither: &Dither,
+    image: &[u8],
+    settings: &LineArt,
+) -> Result<()> {
+    if image.len() < dither.buffer_len() {
+        return Err(Error::BufferTooSmall);
+    }
+    if settings.fill {
+        canvas.dither_bitmap(rect, dither, image)?;
+    }
+    if rect.width == 0 || rect.height == 0 {
+        return Ok(());
+    }
+
+    let luma: Vec<f32> = dither
+        .pixels(image)
+        .iter()
+        .map(|pixel| pixel.luma() as f32 / 255.0)
+        .collect();
+    let cells = edge_cells(
+        settings,
+        &luma,
+        dither.width(),
+        dither.height(),
+        rect.width,
+        rect.height,
+    );
+
+    // (-1, -1) reads the current attribute
+    let previous = canvas.get_attr(&Point { x: -1, y: -1 });
+    for (i, glyph) in glyphs(settings, &cells, rect.width).into_iter().enumerate() {
+        let glyph = match glyph {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+
+        let point = Point {
+            x: rect.x + (i % rect.width) as i32,
+            y: rect.y + (i / rect.width) as i32,
+        };
+        let background = if settings.fill {
+            canvas.get_attr(&point).ansi_bg().unwrap_or(Color::Black)
+        } else {
+            Color::Black
+        };
+
+        canvas.set_attr(Attr::new(settings.color, background));
+        canvas.put_char(&point, glyph as u32);
+    }
+    canvas.set_attr(previous);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edge_cells, glyphs, EdgeDetector, EdgeGlyphs, LineArt};
+
+    // Black on the left or top half, white on the other one
+    fn step(vertical: bool) -> Vec<f32> {
+        (0..32 * 32)
+            .map(|i| {
+                let position = if vertical { i % 32 } else { i / 32 };
+                if position < 16 {
+                    0.0
+                } else {
+                    1.0
+                }
+            })
+            .collect()
+    }
+
+    fn draw(settings: &LineArt, luma: &[f32]) -> Vec<Option<char>> {
+        let cells = edge_cells(settings, luma, 32, 32, 8, 4);
+        glyphs(settings, &cells, 8)
+    }
+
+    #[test]
+    fn directions() {
+        for &detector in &[EdgeDetector::Sobel, EdgeDetector::Canny] {
+            let settings = LineArt::new(detector);
+
+            let vertical = draw(&settings, &step(true));
+            assert!(vertical.contains(&Some('|')));
+            assert!(vertical.iter().all(|g| g.is_none() || *g == Some('|')));
+
+            let horizontal = draw(&settings, &step(false));
+            assert!(horizontal
+                .iter()
+                .any(|g| *g == Some('-') || *g == Some('_')));
+            assert!(!horizontal.contains(&Some('|')));
+
+            let diagonal: Vec<f32> = (0..32 * 32)
+                .map(|i| if i % 32 + i / 32 < 32 { 0.0 } else { 1.0 })
+                .collect();
+            assert!(draw(&settings, &diagonal).contains(&Some('/')));
+
+            let flat = vec![0.5; 32 * 32];
+            assert!(draw(&settings, &flat).iter().all(Option::is_none));
+        }
+    }
+
+    #[test]
+    fn box_drawing() {
+        let settings = LineArt::default().glyphs(EdgeGlyphs::BoxDrawing);
+        let vertical = draw(&settings, &step(true));
+
+        assert!(vertical.contains(&Some('│')));
+    }
+}
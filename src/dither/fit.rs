@@ -0,0 +1,289 @@
+use std::{borrow::Borrow, str::FromStr};
+
+use crate::{
+    canvas::Canvas, dither::Dither, error::Error, result::Result, Boundaries, Point, Rectangle,
+};
+
+// Terminal cells are roughly twice as tall as wide.
+const DEFAULT_CELL_ASPECT: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Fit {
+    // Whole image visible, letterboxed
+    #[default]
+    Contain,
+    // Whole area covered, image cropped
+    Cover,
+    // Image distorted to the area
+    Stretch,
+    FillWidth,
+    FillHeight,
+    // One pixel per column, whatever the area
+    Native,
+}
+
+impl ToString for Fit {
+    fn to_string(&self) -> String {
+        match self {
+            Fit::Contain => "contain",
+            Fit::Cover => "cover",
+            Fit::Stretch => "stretch",
+            Fit::FillWidth => "fill-width",
+            Fit::FillHeight => "fill-height",
+            Fit::Native => "native",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for Fit {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match raw.to_lowercase().borrow() {
+            "contain" => Ok(Self::Contain),
+            "cover" => Ok(Self::Cover),
+            "stretch" => Ok(Self::Stretch),
+            "fill-width" => Ok(Self::FillWidth),
+            "fill-height" => Ok(Self::FillHeight),
+            "native" => Ok(Self::Native),
+            "default" => Ok(Self::default()),
+            _ => Err(Error::InvalidDitherParam("fit")),
+        }
+    }
+}
+
+// Where an image goes inside an area of cells.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fitting {
+    fit: Fit,
+    cell_aspect: f32,
+    centered: bool,
+    letterbox: bool,
+}
+
+impl Default for Fitting {
+    fn default() -> Self {
+        Fitting {
+            fit: Fit::default(),
+            cell_aspect: DEFAULT_CELL_ASPECT,
+            centered: true,
+            letterbox: true,
+        }
+    }
+}
+
+impl Fitting {
+    pub fn new(fit: Fit) -> Fitting {
+        Fitting {
+            fit,
+            ..Self::default()
+        }
+    }
+
+    // Width over height of a cell, see `Font::cell_aspect` and
+    // `Display::cell_aspect`.
+    pub fn cell_aspect(mut self, cell_aspect: f32) -> Fitting {
+        self.cell_aspect = cell_aspect;
+        self
+    }
+
+    // Otherwise the image sticks to the top left corner of the area.
+    pub fn centered(mut self, centered: bool) -> Fitting {
+        self.centered = centered;
+        self
+    }
+
+    // Whether the rest of the area is cleared with the canvas' current
+    // attribute or left untouched.
+    pub fn letterbox(mut self, letterbox: bool) -> Fitting {
+        self.letterbox = letterbox;
+        self
+    }
+
+    pub fn fit(&self) -> Fit {
+        self.fit
+    }
+
+    // Cells the image takes, can overflow `area` with `Fit::Cover` and
+    // `Fit::Native`.
+    pub fn rect(&self, image: &Boundaries, area: &Rectangle) -> Rectangle {
+        let (iw, ih) = (image.width.max(1) as f32, image.height.max(1) as f32);
+        let aspect = if self.cell_aspect.is_finite() && self.cell_aspect > 0.0 {
+            self.cell_aspect
+        } else {
+            DEFAULT_CELL_ASPECT
+        };
+
+        // Rows needed for a given number of columns, and the other way around
+        let rows = |columns: f32| columns * aspect * ih / iw;
+        let columns = |rows: f32| rows * iw / (ih * aspect);
+
+        let (aw, ah) = (area.width as f32, area.height as f32);
+        let (width, height) = match self.fit {
+            Fit::Stretch => (aw, ah),
+            Fit::FillWidth => (aw, rows(aw)),
+            Fit::FillHeight => (columns(ah), ah),
+            Fit::Contain if rows(aw) <= ah => (aw, rows(aw)),
+            Fit::Contain => (columns(ah), ah),
+            Fit::Cover if rows(aw) >= ah => (aw, rows(aw)),
+            Fit::Cover => (columns(ah), ah),
+            Fit::Native => (iw, rows(iw)),
+        };
+
+        let width = (width.round() as usize).max(1);
+        let height = (height.round() as usize).max(1);
+
+        let (x, y) = if self.centered {
+            (
+                area.x + (area.width as i32 - width as i32) / 2,
+                area.y + (area.height as i32 - height as i32) / 2,
+            )
+        } else {
+            (area.x, area.y)
+        };
+
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+// Dithers in a scratch canvas the size of `area` so nothing spills out of
+// it, then blits the result back.
+pub(crate) fn dither_bitmap(
+    canvas: &mut Canvas,
+    area: &Rectangle,
+    dither: &Dither,
+    image: &[u8],
+    fitting: &Fitting,
+) -> Result<Rectangle> {
+    let rect = fitting.rect(
+        &Boundaries {
+            width: dither.width(),
+            height: dither.height(),
+        },
+        area,
+    );
+
+    if area.width == 0 || area.height == 0 {
+        return Ok(rect);
+    }
+
+    let size = Boundaries {
+        width: area.width,
+        height: area.height,
+    };
+    let mut scratch = Canvas::new(&size)?;
+    // (-1, -1) reads the current attribute
+    scratch.set_attr(canvas.get_attr(&Point { x: -1, y: -1 }));
+    scratch.clear();
+    scratch.dither_bitmap(
+        &Rectangle {
+            x: rect.x - area.x,
+            y: rect.y - area.y,
+            ..rect
+        },
+        dither,
+        image,
+    )?;
+
+    let origin = Point {
+        x: area.x,
+        y: area.y,
+    };
+
+    if fitting.letterbox {
+        canvas.blit(&origin, &scratch, None)?;
+    } else {
+        // Only the cells under the image go through the mask
+        let mut mask = Canvas::new(&size)?;
+        let left = (rect.x - area.x).max(0);
+        let top = (rect.y - area.y).max(0);
+        let right = (rect.x - area.x + rect.width as i32).min(area.width as i32);
+        let bottom = (rect.y - area.y + rect.height as i32).min(area.height as i32);
+
+        for y in top..bottom {
+            for x in left..right {
+                mask.put_char(&Point { x, y }, '#' as u32);
+            }
+        }
+        canvas.blit(&origin, &scratch, Some(&mask))?;
+    }
+
+    Ok(rect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fit, Fitting};
+    use crate::{Boundaries, Rectangle};
+
+    fn area() -> Rectangle {
+        Rectangle {
+            x: 0,
+            y: 0,
+            width: 80,
+            height: 24,
+        }
+    }
+
+    fn rect(x: i32, y: i32, width: usize, height: usize) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn fit() {
+        let square = Boundaries {
+            width: 100,
+            height: 100,
+        };
+
+        assert_eq!(
+            Fitting::new(Fit::Contain).rect(&square, &area()),
+            rect(16, 0, 48, 24)
+        );
+        assert_eq!(
+            Fitting::new(Fit::Cover).rect(&square, &area()),
+            rect(0, -8, 80, 40)
+        );
+        assert_eq!(Fitting::new(Fit::Stretch).rect(&square, &area()), area());
+        assert_eq!(
+            Fitting::new(Fit::FillWidth).rect(&square, &area()),
+            rect(0, -8, 80, 40)
+        );
+        assert_eq!(
+            Fitting::new(Fit::FillHeight).rect(&square, &area()),
+            rect(16, 0, 48, 24)
+        );
+        assert_eq!(
+            Fitting::new(Fit::Native)
+                .centered(false)
+                .rect(&square, &area()),
+            rect(0, 0, 100, 50)
+        );
+        assert_eq!(
+            Fitting::new(Fit::Contain)
+                .cell_aspect(1.0)
+                .centered(false)
+                .rect(&square, &area()),
+            rect(0, 0, 24, 24)
+        );
+    }
+
+    #[test]
+    fn names() {
+        for &fit in &[Fit::Contain, Fit::Cover, Fit::FillWidth, Fit::Native] {
+            assert_eq!(fit.to_string().parse::<Fit>().unwrap(), fit);
+        }
+        assert!("zoom".parse::<Fit>().is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use crate::{canvas::Canvas, color::Rgba, dither::Dither, error::Error, result::Result, Rectangle};
+
+// How the source luminance gets remapped before dithering.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Levels {
+    // Flattens the luminance histogram
+    Equalize,
+    // Stretches the `low` to `high` percentiles (0.0..=1.0) to the full range
+    AutoLevels { low: f32, high: f32 },
+    // Equalizes `tiles * tiles` regions separately, capping histogram bins
+    // at `clip_limit` times their average to keep noise down
+    Clahe { tiles: usize, clip_limit: f32 },
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self::AutoLevels {
+            low: 0.01,
+            high: 0.99,
+        }
+    }
+}
+
+// Dither settings worked out from a histogram. Only gamma, libcaca stores
+// brightness and contrast but never uses them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Adjustments {
+    pub gamma: f32,
+}
+
+impl Adjustments {
+    pub fn apply(&self, dither: &Dither) -> Result<()> {
+        dither.set_gamma(self.gamma)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram([u32; 256]);
+
+impl Histogram {
+    // Luminance of the whole bitmap.
+    pub fn from_bitmap(dither: &Dither, image: &[u8]) -> Histogram {
+        Self::from_luma(dither.pixels(image).iter().map(Rgba::luma))
+    }
+
+    pub fn from_luma<I: IntoIterator<Item = u8>>(values: I) -> Histogram {
+        let mut bins = [0u32; 256];
+        for value in values {
+            bins[value as usize] += 1;
+        }
+
+        Histogram(bins)
+    }
+
+    pub fn bins(&self) -> &[u32; 256] {
+        &self.0
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().map(|&n| n as u64).sum()
+    }
+
+    // Smallest level with at least `fraction` of the pixels at or below it.
+    pub fn percentile(&self, fraction: f32) -> u8 {
+        let target = (self.total() as f64 * fraction.clamp(0.0, 1.0) as f64).ceil() as u64;
+        let mut seen = 0u64;
+
+        for (level, &n) in self.0.iter().enumerate() {
+            seen += n as u64;
+            if seen >= target.max(1) {
+                return level as u8;
+            }
+        }
+
+        0xff
+    }
+
+    pub fn mean(&self) -> f32 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let sum: u64 = self
+            .0
+            .iter()
+            .enumerate()
+            .map(|(level, &n)| level as u64 * n as u64)
+            .sum();
+
+        sum as f32 / total as f32 / 255.0
+    }
+
+    // Gamma bringing the mean to mid gray, libcaca raises to 1 / gamma.
+    // Stretching the range takes `Canvas::dither_bitmap_levels`, which
+    // remaps the bitmap itself.
+    pub fn suggest(&self) -> Adjustments {
+        let mean = self.mean().clamp(0.01, 0.99);
+
+        Adjustments {
+            gamma: (mean.ln() / 0.5f32.ln()).clamp(0.2, 5.0),
+        }
+    }
+
+    pub fn equalize_lut(&self) -> [u8; 256] {
+        let mut lut = [0u8; 256];
+        let total = self.total();
+        let first = self.0.iter().find(|&&n| n > 0).copied().unwrap_or(0) as u64;
+
+        if total <= first {
+            for (level, entry) in lut.iter_mut().enumerate() {
+                *entry = level as u8;
+            }
+            return lut;
+        }
+
+        let mut seen = 0u64;
+        for (level, &n) in self.0.iter().enumerate() {
+            seen += n as u64;
+            lut[level] = ((seen.saturating_sub(first)) * 255 / (total - first)) as u8;
+        }
+
+        lut
+    }
+
+    pub fn auto_levels_lut(&self, low: f32, high: f32) -> [u8; 256] {
+        let (low, high) = (self.percentile(low) as i32, self.percentile(high) as i32);
+        let mut lut = [0u8; 256];
+
+        for (level, entry) in lut.iter_mut().enumerate() {
+            *entry = if high <= low {
+                level as u8
+            } else {
+                ((level as i32 - low) * 255 / (high - low)).clamp(0, 255) as u8
+            };
+        }
+
+        lut
+    }
+
+    // Caps bins at `limit` and spreads what was cut evenly.
+    fn clip(&mut self, limit: u32) {
+        let mut excess = 0u64;
+        for bin in self.0.iter_mut() {
+            if *bin > limit {
+                excess += (*bin - limit) as u64;
+                *bin = limit;
+            }
+        }
+
+        let share = (excess / 256) as u32;
+        let rest = (excess % 256) as usize;
+        for (level, bin) in self.0.iter_mut().enumerate() {
+            *bin += share + (level < rest) as u32;
+        }
+    }
+}
+
+// New luminance for every pixel of a `width * height` luminance map.
+pub(crate) fn remap(levels: &Levels, luma: &[u8], width: usize, height: usize) -> Vec<u8> {
+    match *levels {
+        Levels::Equalize => {
+            let lut = Histogram::from_luma(luma.iter().copied()).equalize_lut();
+            luma.iter().map(|&l| lut[l as usize]).collect()
+        }
+        Levels::AutoLevels { low, high } => {
+            let lut = Histogram::from_luma(luma.iter().copied()).auto_levels_lut(low, high);
+            luma.iter().map(|&l| lut[l as usize]).collect()
+        }
+        Levels::Clahe { tiles, clip_limit } => clahe(luma, width, height, tiles, clip_limit),
+    }
+}
+
+fn clahe(luma: &[u8], width: usize, height: usize, tiles: usize, clip_limit: f32) -> Vec<u8> {
+    if luma.is_empty() || width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let tiles = tiles.max(1);
+    let tile_w = width / tiles + !width.is_multiple_of(tiles) as usize;
+    let tile_h = height / tiles + !height.is_multiple_of(tiles) as usize;
+    // Rounding up can leave the last tiles empty
+    let tx = width / tile_w + !width.is_multiple_of(tile_w) as usize;
+    let ty = height / tile_h + !height.is_multiple_of(tile_h) as usize;
+
+    let mut luts = Vec::with_capacity(tx * ty);
+    for j in 0..ty {
+        for i in 0..tx {
+            let (x0, y0) = (i * tile_w, j * tile_h);
+            let (x1, y1) = ((x0 + tile_w).min(width), (y0 + tile_h).min(height));
+
+            let mut histogram = Histogram::from_luma(
+                (y0..y1).flat_map(|y| (x0..x1).map(move |x| luma[y * width + x])),
+            );
+            let area = ((x1 - x0) * (y1 - y0)) as f32;
+            histogram.clip(((clip_limit.max(1.0) * area / 256.0).ceil() as u32).max(1));
+            luts.push(histogram.equalize_lut());
+        }
+    }
+
+    // Blends the four closest tile centers
+    let axis = |position: usize, size: usize, count: usize| {
+        let f = ((position as f32 + 0.5) / size as f32 - 0.5).max(0.0);
+        let first = (f as usize).min(count - 1);
+        let second = (first + 1).min(count - 1);
+        (first, second, (f - first as f32).min(1.0))
+    };
+
+    let mut out = Vec::with_capacity(luma.len());
+    for y in 0..height {
+        let (j0, j1, fy) = axis(y, tile_h, ty);
+        for x in 0..width {
+            let (i0, i1, fx) = axis(x, tile_w, tx);
+            let l = luma[y * width + x] as usize;
+            let at = |i: usize, j: usize| luts[j * tx + i][l] as f32;
+
+            let top = at(i0, j0) * (1.0 - fx) + at(i1, j0) * fx;
+            let bottom = at(i0, j1) * (1.0 - fx) + at(i1, j1) * fx;
+            out.push((top * (1.0 - fy) + bottom * fy).round() as u8);
+        }
+    }
+
+    out
+}
+
+// Remaps the bitmap's luminance, keeping hues, and hands it to libcaca with
+// the dither's settings.
+pub(crate) fn dither_bitmap(
+    canvas: &mut Canvas,
+    rect: &Rectangle,
+    dither: &Dither,
+    image: &[u8],
+    levels: &Levels,
+) -> Result<()> {
+    if image.len() < dither.buffer_len() {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let pixels = dither.pixels(image);
+    let luma: Vec<u8> = pixels.iter().map(Rgba::luma).collect();
+    let remapped = remap(levels, &luma, dither.width(), dither.height());
+
+    let mut bitmap = Vec::with_capacity(pixels.len() * 4);
+    for ((pixel, &old), &new) in pixels.iter().zip(&luma).zip(&remapped) {
+        if old == 0 {
+            bitmap.extend_from_slice(&[new, new, new, pixel.a]);
+        } else {
+            let scale = |c: u8| (c as u32 * new as u32 / old as u32).min(0xff) as u8;
+            bitmap.extend_from_slice(&[scale(pixel.r), scale(pixel.g), scale(pixel.b), pixel.a]);
+        }
+    }
+
+    let remapped = dither.rgba32_like(dither.width(), dither.height())?;
+    canvas.dither_bitmap(rect, &remapped, &bitmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remap, Histogram, Levels};
+
+    #[test]
+    fn histogram() {
+        let histogram = Histogram::from_luma(0..100);
+        assert_eq!(histogram.total(), 100);
+        assert_eq!(histogram.percentile(0.5), 49);
+        assert_eq!(histogram.percentile(1.0), 99);
+
+        // Dark, gamma has to lift it
+        assert!(histogram.suggest().gamma > 2.0);
+        assert!((Histogram::from_luma(0..=255).suggest().gamma - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn levels() {
+        let dark: Vec<u8> = (0..64u32).map(|i| (i % 32) as u8).collect();
+
+        for levels in &[
+            Levels::Equalize,
+            Levels::default(),
+            Levels::Clahe {
+                tiles: 2,
+                clip_limit: 4.0,
+            },
+        ] {
+            let out = remap(levels, &dark, 8, 8);
+            assert_eq!(out.len(), dark.len());
+            assert!(*out.iter().max().unwrap() > 200);
+        }
+
+        let flat = vec![42u8; 16];
+        assert_eq!(remap(&Levels::default(), &flat, 4, 4), flat);
+    }
+}
@@ -0,0 +1,194 @@
+use std::convert::TryFrom;
+
+use crate::{
+    attr::Attr,
+    canvas::Canvas,
+    color::Rgba,
+    dither::{diffusion::resample, Dither},
+    error::Error,
+    font::Font,
+    result::Result,
+    Boundaries, Color, Point, Rectangle,
+};
+
+// Glyphs ordered from the emptiest to the densest, each with its density in
+// 0.0..=1.0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphRamp(Vec<(char, f32)>);
+
+impl GlyphRamp {
+    // Trusts the given order, densities are evenly spaced.
+    pub fn new<S: AsRef<str>>(glyphs: S) -> Result<GlyphRamp> {
+        let glyphs: Vec<char> = glyphs.as_ref().chars().collect();
+        if glyphs.is_empty() {
+            return Err(Error::InvalidCharset);
+        }
+
+        let last = (glyphs.len() - 1).max(1) as f32;
+        Ok(GlyphRamp(
+            glyphs
+                .into_iter()
+                .enumerate()
+                .map(|(i, ch)| (ch, i as f32 / last))
+                .collect(),
+        ))
+    }
+
+    // Orders the glyphs by how much of their cell `font` covers, glyphs the
+    // font doesn't have end up as empty as a space.
+    pub fn from_font<S: AsRef<str>>(glyphs: S, font: &Font) -> Result<GlyphRamp> {
+        let glyphs: Vec<char> = glyphs.as_ref().chars().collect();
+        if glyphs.is_empty() {
+            return Err(Error::InvalidCharset);
+        }
+
+        // Two cells so fullwidth glyphs fit
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 2,
+            height: 1,
+        })?;
+        canvas.set_color_ansi(Color::White as u8, Color::Black as u8)?;
+
+        let mut ramp = Vec::with_capacity(glyphs.len());
+        for ch in glyphs {
+            canvas.clear();
+            let cells = canvas.put_char(&Point { x: 0, y: 0 }, ch as u32).max(1);
+            let image = canvas.render_rgba(font)?;
+
+            let lit: u64 = image
+                .data
+                .chunks_exact(4)
+                .map(|pixel| pixel[0] as u64)
+                .sum();
+            let area = (font.width() * font.height() * cells) as u64 * 0xff;
+
+            ramp.push((ch, lit as f32 / area.max(1) as f32));
+        }
+
+        ramp.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let (min, max) = (ramp[0].1, ramp[ramp.len() - 1].1);
+        if max > min {
+            for entry in &mut ramp {
+                entry.1 = (entry.1 - min) / (max - min);
+            }
+        } else {
+            let last = (ramp.len() - 1).max(1) as f32;
+            for (i, entry) in ramp.iter_mut().enumerate() {
+                entry.1 = i as f32 / last;
+            }
+        }
+
+        Ok(GlyphRamp(ramp))
+    }
+
+    pub fn ascii() -> GlyphRamp {
+        Self::new(" .:-=+*#%@").expect("ramp is not empty")
+    }
+
+    pub fn braille() -> GlyphRamp {
+        Self::new(" ⠁⠃⠇⡇⡏⡟⡿⣿").expect("ramp is not empty")
+    }
+
+    pub fn quadrants() -> GlyphRamp {
+        Self::new(" ▘▚▙█").expect("ramp is not empty")
+    }
+
+    pub fn glyphs(&self) -> &[(char, f32)] {
+        &self.0
+    }
+
+    // The glyph whose density is the closest to `density`.
+    pub fn glyph(&self, density: f32) -> char {
+        self.0
+            .iter()
+            .min_by(|a, b| (a.1 - density).abs().total_cmp(&(b.1 - density).abs()))
+            .map(|&(ch, _)| ch)
+            .unwrap_or(' ')
+    }
+}
+
+// Lets libcaca pick colours as usual, then swaps its glyphs for the ramp's
+// one matching the luminance of each cell. The brightest of libcaca's two
+// colours is kept as the glyph colour, over black.
+pub(crate) fn dither_bitmap(
+    canvas: &mut Canvas,
+    rect: &Rectangle,
+    dither: &Dither,
+    image: &[u8],
+    ramp: &GlyphRamp,
+) -> Result<()> {
+    canvas.dither_bitmap(rect, dither, image)?;
+    if rect.width == 0 || rect.height == 0 {
+        return Ok(());
+    }
+
+    let cells = resample(dither, image, rect.width, rect.height);
+    // (-1, -1) reads the current attribute
+    let previous = canvas.get_attr(&Point { x: -1, y: -1 });
+    let brightness = |color: Color| {
+        Rgba::try_from(color)
+            .map(|c| c.r as u32 + c.g as u32 + c.b as u32)
+            .unwrap_or(0)
+    };
+
+    for cy in 0..rect.height {
+        let y = rect.y + cy as i32;
+        if y < 0 || y as usize >= canvas.height() {
+            continue;
+        }
+
+        let mut cx = 0;
+        while cx < rect.width {
+            let x = rect.x + cx as i32;
+            if x < 0 || x as usize >= canvas.width() {
+                cx += 1;
+                continue;
+            }
+
+            let point = Point { x, y };
+            let cell = cells[cy * rect.width + cx];
+            let luminance = (0.2126 * cell[0] + 0.7152 * cell[1] + 0.0722 * cell[2]) * cell[3];
+
+            let attr = canvas.get_attr(&point);
+            let color = match (attr.ansi_fg(), attr.ansi_bg()) {
+                (Ok(fg), Ok(bg)) if brightness(bg) > brightness(fg) => bg,
+                (Ok(fg), _) => fg,
+                (Err(_), _) => Color::White,
+            };
+
+            canvas.set_attr(Attr::new(color, Color::Black));
+            cx += canvas.put_char(&point, ramp.glyph(luminance) as u32).max(1);
+        }
+    }
+
+    canvas.set_attr(previous);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GlyphRamp;
+    use crate::Font;
+
+    #[test]
+    fn ramp() {
+        let ramp = GlyphRamp::ascii();
+        assert_eq!(ramp.glyph(0.0), ' ');
+        assert_eq!(ramp.glyph(1.0), '@');
+        assert_eq!(ramp.glyph(0.56), '+');
+        // No panic on NaN
+        assert!(" .:-=+*#%@".contains(ramp.glyph(f32::NAN)));
+        assert!(GlyphRamp::new("").is_err());
+    }
+
+    #[test]
+    fn from_font() {
+        let font = Font::new(Font::list().first().unwrap()).unwrap();
+        let ramp = GlyphRamp::from_font("@. ", &font).unwrap();
+        let glyphs: Vec<char> = ramp.glyphs().iter().map(|&(ch, _)| ch).collect();
+
+        assert_eq!(glyphs, vec![' ', '.', '@']);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{borrow::Borrow, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dither::{Antialias, Dither, DitherAlgorithm, DitherCharset, DitherColor},
+    error::Error,
+    result::Result,
+};
+
+// Every setting of a `Dither` as one value. With the `serde` feature it
+// (de)serializes with libcaca's names for the modes, missing fields taking
+// libcaca's defaults.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DitherSettings {
+    pub brightness: f32,
+    pub gamma: f32,
+    pub contrast: f32,
+    pub antialias: Antialias,
+    pub color: DitherColor,
+    pub charset: DitherCharset,
+    pub algorithm: DitherAlgorithm,
+}
+
+impl Default for DitherSettings {
+    fn default() -> Self {
+        DitherSettings {
+            brightness: 1.0,
+            gamma: 1.0,
+            contrast: 1.0,
+            antialias: Antialias::default(),
+            color: DitherColor::default(),
+            charset: DitherCharset::default(),
+            algorithm: DitherAlgorithm::Fstein,
+        }
+    }
+}
+
+impl DitherSettings {
+    pub fn from_dither(dither: &Dither) -> Result<DitherSettings> {
+        Ok(DitherSettings {
+            brightness: dither.brightness(),
+            gamma: dither.gamma(),
+            contrast: dither.contrast(),
+            antialias: dither.antialias()?,
+            color: dither.color()?,
+            charset: dither.charset()?,
+            algorithm: dither.algorithm()?,
+        })
+    }
+
+    pub fn apply(&self, dither: &Dither) -> Result<()> {
+        dither.set_brightness(self.brightness)?;
+        dither.set_gamma(self.gamma)?;
+        dither.set_contrast(self.contrast)?;
+        dither.set_antialias(self.antialias)?;
+        dither.set_color(self.color)?;
+        dither.set_charset(self.charset)?;
+        dither.set_algorithm(self.algorithm)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Preset {
+    // Full colour, block characters, error diffusion
+    Photo,
+    // Hard edged mono ASCII, no dithering noise around strokes
+    LineArt,
+    // Mono ASCII meant for paper, a bit darker
+    MonoPrint,
+    // The 16 ANSI colours with a visible ordered pattern
+    Retro16,
+}
+
+impl Preset {
+    pub fn all() -> &'static [Preset] {
+        &[
+            Preset::Photo,
+            Preset::LineArt,
+            Preset::MonoPrint,
+            Preset::Retro16,
+        ]
+    }
+
+    pub fn settings(&self) -> DitherSettings {
+        let defaults = DitherSettings::default();
+
+        match self {
+            Self::Photo => DitherSettings {
+                charset: DitherCharset::Blocks,
+                ..defaults
+            },
+            Self::LineArt => DitherSettings {
+                antialias: Antialias::None,
+                color: DitherColor::Mono,
+                algorithm: DitherAlgorithm::None,
+                ..defaults
+            },
+            Self::MonoPrint => DitherSettings {
+                // libcaca raises to 1 / gamma, below 1 darkens
+                gamma: 0.8,
+                color: DitherColor::Mono,
+                charset: DitherCharset::Shades,
+                ..defaults
+            },
+            Self::Retro16 => DitherSettings {
+                antialias: Antialias::None,
+                color: DitherColor::Sixteen,
+                charset: DitherCharset::Blocks,
+                algorithm: DitherAlgorithm::Ordered4,
+                ..defaults
+            },
+        }
+    }
+}
+
+impl From<Preset> for DitherSettings {
+    fn from(preset: Preset) -> Self {
+        preset.settings()
+    }
+}
+
+impl ToString for Preset {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Photo => "photo",
+            Self::LineArt => "line-art",
+            Self::MonoPrint => "mono-print",
+            Self::Retro16 => "retro-16",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for Preset {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match raw.to_lowercase().borrow() {
+            "photo" => Ok(Self::Photo),
+            "line-art" => Ok(Self::LineArt),
+            "mono-print" => Ok(Self::MonoPrint),
+            "retro-16" => Ok(Self::Retro16),
+            _ => Err(Error::InvalidDitherParam("preset")),
+        }
+    }
+}
+
+// The modes go through their libcaca names, like the `*_name` accessors.
+#[cfg(feature = "serde")]
+macro_rules! serde_by_name {
+    ($($ty:ty),*) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize<S: serde::Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> std::result::Result<S::Ok, S::Error> {
+                    serializer.serialize_str(&self.to_string())
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: serde::Deserializer<'de>>(
+                    deserializer: D,
+                ) -> std::result::Result<Self, D::Error> {
+                    let name = String::deserialize(deserializer)?;
+                    name.parse().map_err(serde::de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "serde")]
+serde_by_name!(
+    Antialias,
+    DitherColor,
+    DitherCharset,
+    DitherAlgorithm,
+    Preset
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{DitherSettings, Preset};
+    use crate::dither::{Dither, DitherAlgorithm, DitherColor, PixelFormat};
+
+    #[test]
+    fn presets() {
+        for preset in Preset::all() {
+            assert_eq!(preset.to_string().parse::<Preset>().unwrap(), *preset);
+        }
+        assert!("sepia".parse::<Preset>().is_err());
+
+        let retro = DitherSettings::from(Preset::Retro16);
+        assert_eq!(retro.color, DitherColor::Sixteen);
+        assert_eq!(retro.algorithm, DitherAlgorithm::Ordered4);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let dither = Dither::builder(PixelFormat::Rgb24, 4, 4).build().unwrap();
+        assert_eq!(
+            DitherSettings::from_dither(&dither).unwrap(),
+            DitherSettings::default()
+        );
+
+        for preset in Preset::all() {
+            let settings = preset.settings();
+            settings.apply(&dither).unwrap();
+            assert_eq!(dither.settings().unwrap(), settings);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let settings = Preset::Retro16.settings();
+        let json = serde_json::to_string(&settings).unwrap();
+        assert!(json.contains(r#""color":"16""#));
+        assert_eq!(
+            serde_json::from_str::<DitherSettings>(&json).unwrap(),
+            settings
+        );
+
+        let partial: DitherSettings = serde_json::from_str(r#"{"algorithm":"ordered8"}"#).unwrap();
+        assert_eq!(partial.algorithm, DitherAlgorithm::Ordered8);
+        assert_eq!(partial.gamma, 1.0);
+        assert!(serde_json::from_str::<DitherSettings>(r#"{"charset":"emoji"}"#).is_err());
+    }
+}
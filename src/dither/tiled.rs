@@ -0,0 +1,359 @@
+use std::{
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    attr::Attr,
+    canvas::Canvas,
+    dither::{Dither, DitherAlgorithm, DitherSettings, PixelFormat},
+    error::Error,
+    result::Result,
+    Boundaries, Point, Rectangle,
+};
+
+// Side of the largest ordered dithering matrix, tiles start on a multiple
+// of it so the pattern lines up across borders.
+const MATRIX: usize = 8;
+
+// How `Canvas::dither_bitmap_tiled` splits the work.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tiling {
+    tile_width: usize,
+    tile_height: usize,
+    threads: usize,
+    overlap: usize,
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Tiling {
+            tile_width: 64,
+            tile_height: 16,
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            overlap: MATRIX,
+        }
+    }
+}
+
+impl Tiling {
+    pub fn new() -> Tiling {
+        Self::default()
+    }
+
+    // In cells.
+    pub fn tile_size(mut self, width: usize, height: usize) -> Tiling {
+        self.tile_width = width.max(1);
+        self.tile_height = height.max(1);
+        self
+    }
+
+    // Defaults to the available parallelism.
+    pub fn threads(mut self, threads: usize) -> Tiling {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // Cells dithered around every tile and thrown away, so error diffusion
+    // is already going when it reaches the tile's own cells.
+    pub fn overlap(mut self, overlap: usize) -> Tiling {
+        self.overlap = overlap;
+        self
+    }
+
+    // Tiles covering `rect`, row by row.
+    pub fn tiles(&self, rect: &Rectangle) -> Vec<Rectangle> {
+        let mut tiles = Vec::new();
+
+        for y in (0..rect.height).step_by(self.tile_height) {
+            for x in (0..rect.width).step_by(self.tile_width) {
+                tiles.push(Rectangle {
+                    x: rect.x + x as i32,
+                    y: rect.y + y as i32,
+                    width: self.tile_width.min(rect.width - x),
+                    height: self.tile_height.min(rect.height - y),
+                });
+            }
+        }
+
+        tiles
+    }
+
+    // `tile` grown by the overlap, without leaving `rect`. The left edge
+    // stays on the ordered dithering grid of `rect`.
+    fn extend(&self, rect: &Rectangle, tile: &Rectangle) -> Rectangle {
+        let left = (tile.x - rect.x) as usize;
+        let top = (tile.y - rect.y) as usize;
+        let left = left.saturating_sub(self.overlap) / MATRIX * MATRIX;
+        let top = top.saturating_sub(self.overlap);
+        let right = ((tile.x - rect.x) as usize + tile.width + self.overlap).min(rect.width);
+        let bottom = ((tile.y - rect.y) as usize + tile.height + self.overlap).min(rect.height);
+
+        Rectangle {
+            x: rect.x + left as i32,
+            y: rect.y + top as i32,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
+// One tile's worth of work, everything owned to cross threads.
+struct Job {
+    tile: Rectangle,
+    extended: Rectangle,
+    // Source pixels under `extended`
+    source: Rectangle,
+}
+
+struct Shared {
+    settings: DitherSettings,
+    attr: Attr,
+    // RGBA32 copy of the whole bitmap
+    image: Vec<u8>,
+    width: usize,
+    jobs: Vec<Job>,
+    next: AtomicUsize,
+    // libcaca keeps the ordered dithering state in globals
+    serial: Option<Mutex<()>>,
+}
+
+// Same result as `Canvas::dither_bitmap`, give or take the tile borders,
+// with the tiles dithered on worker threads.
+pub(crate) fn dither_bitmap(
+    canvas: &mut Canvas,
+    rect: &Rectangle,
+    dither: &Dither,
+    image: &[u8],
+    tiling: &Tiling,
+) -> Result<()> {
+    if image.len() < dither.buffer_len() {
+        return Err(Error::BufferTooSmall);
+    }
+    if rect.width == 0 || rect.height == 0 || dither.width() == 0 || dither.height() == 0 {
+        return Ok(());
+    }
+
+    let (width, height) = (dither.width(), dither.height());
+    // Cells [from, to) of `length` to pixels of `size`, never empty
+    let span = |from: usize, to: usize, length: usize, size: usize| {
+        let start = (from * size / length).min(size - 1);
+        (start, (to * size / length).clamp(start + 1, size))
+    };
+
+    let jobs = tiling
+        .tiles(rect)
+        .into_iter()
+        .map(|tile| {
+            let extended = tiling.extend(rect, &tile);
+            let left = (extended.x - rect.x) as usize;
+            let top = (extended.y - rect.y) as usize;
+            let (x0, x1) = span(left, left + extended.width, rect.width, width);
+            let (y0, y1) = span(top, top + extended.height, rect.height, height);
+
+            Job {
+                tile,
+                extended,
+                source: Rectangle {
+                    x: x0 as i32,
+                    y: y0 as i32,
+                    width: x1 - x0,
+                    height: y1 - y0,
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let settings = dither.settings()?;
+    let serial = match settings.algorithm {
+        DitherAlgorithm::Ordered2 | DitherAlgorithm::Ordered4 | DitherAlgorithm::Ordered8 => {
+            Some(Mutex::new(()))
+        }
+        _ => None,
+    };
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for pixel in dither.pixels(image) {
+        rgba.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+    }
+
+    let threads = tiling.threads.min(jobs.len()).max(1);
+    let shared = Arc::new(Shared {
+        settings,
+        attr: canvas.get_attr(&Point { x: -1, y: -1 }),
+        image: rgba,
+        width,
+        jobs,
+        next: AtomicUsize::new(0),
+        serial,
+    });
+
+    let workers = (0..threads)
+        .map(|_| {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || -> Result<Vec<(usize, Canvas<'static>)>> {
+                let mut done = Vec::new();
+                loop {
+                    let id = shared.next.fetch_add(1, Ordering::Relaxed);
+                    match shared.jobs.get(id) {
+                        Some(job) => done.push((id, render(&shared, job)?)),
+                        None => return Ok(done),
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut tiles = Vec::with_capacity(shared.jobs.len());
+    for worker in workers {
+        tiles.extend(worker.join().unwrap_or_else(|e| panic::resume_unwind(e))?);
+    }
+
+    for (id, tile) in tiles {
+        let job = &shared.jobs[id];
+        canvas.blit(
+            &Point {
+                x: job.tile.x,
+                y: job.tile.y,
+            },
+            &tile,
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Dithers the extended tile, keeps the tile's own cells.
+fn render(shared: &Shared, job: &Job) -> Result<Canvas<'static>> {
+    let source = &job.source;
+    let (x, y) = (source.x as usize, source.y as usize);
+    let mut pixels = Vec::with_capacity(source.width * source.height * 4);
+    for row in y..y + source.height {
+        let start = (row * shared.width + x) * 4;
+        pixels.extend_from_slice(&shared.image[start..start + source.width * 4]);
+    }
+
+    let dither = Dither::builder(PixelFormat::Rgba32, source.width, source.height).build()?;
+    shared.settings.apply(&dither)?;
+    // Ordered matrices are indexed by canvas row, padding the top keeps
+    // the rows in phase with the destination canvas
+    let pad = job.extended.y.rem_euclid(MATRIX as i32);
+
+    let mut scratch = Canvas::new(&Boundaries {
+        width: job.extended.width,
+        height: job.extended.height + pad as usize,
+    })?;
+    scratch.set_attr(shared.attr);
+    scratch.clear();
+
+    let area = Rectangle {
+        x: 0,
+        y: pad,
+        width: job.extended.width,
+        height: job.extended.height,
+    };
+    {
+        let _serial = shared.serial.as_ref().map(|lock| lock.lock());
+        scratch.dither_bitmap(&area, &dither, &pixels)?;
+    }
+
+    let mut tile = Canvas::new(&Boundaries {
+        width: job.tile.width,
+        height: job.tile.height,
+    })?;
+    tile.blit(
+        &Point {
+            x: job.extended.x - job.tile.x,
+            y: job.extended.y - job.tile.y + pad,
+        },
+        &scratch,
+        None,
+    )?;
+
+    Ok(tile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tiling;
+    use crate::{dither::PixelFormat, Boundaries, Canvas, Dither, Rectangle};
+
+    #[test]
+    fn tiles() {
+        let rect = Rectangle {
+            x: 3,
+            y: 1,
+            width: 100,
+            height: 20,
+        };
+        let tiling = Tiling::new().tile_size(32, 8).overlap(4);
+        let tiles = tiling.tiles(&rect);
+
+        assert_eq!(tiles.len(), 4 * 3);
+        assert_eq!(
+            tiles.iter().map(|t| t.width * t.height).sum::<usize>(),
+            100 * 20
+        );
+        assert_eq!((tiles[3].x, tiles[3].width), (99, 4));
+        assert_eq!((tiles[11].y, tiles[11].height), (17, 4));
+
+        let extended = tiling.extend(&rect, &tiles[5]);
+        assert_eq!((extended.x, extended.width), (27, 32 + 8 + 4));
+        assert_eq!((extended.y, extended.height), (5, 8 + 8));
+        assert_eq!((extended.x - rect.x) % 8, 0);
+
+        let first = tiling.extend(&rect, &tiles[0]);
+        assert_eq!((first.x, first.y), (rect.x, rect.y));
+    }
+
+    #[test]
+    fn dither_bitmap_tiled() {
+        let (width, height) = (128, 64);
+        let image: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                vec![(x * 2) as u8, (y * 4) as u8, ((x + y) % 256) as u8]
+            })
+            .collect();
+        let dither = Dither::builder(PixelFormat::Rgb24, width, height)
+            .build()
+            .unwrap();
+        let rect = Rectangle {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 32,
+        };
+        let size = Boundaries {
+            width: 64,
+            height: 32,
+        };
+
+        let mut single = Canvas::new(&size).unwrap();
+        single.dither_bitmap(&rect, &dither, &image).unwrap();
+
+        let mut tiled = Canvas::new(&size).unwrap();
+        let tiling = Tiling::new().tile_size(16, 8).threads(4);
+        tiled
+            .dither_bitmap_tiled(&rect, &dither, &image, &tiling)
+            .unwrap();
+
+        let same = single
+            .chars()
+            .iter()
+            .zip(tiled.chars())
+            .filter(|(a, b)| a == b)
+            .count();
+        assert!(same * 10 >= single.chars().len() * 9);
+
+        assert!(tiled
+            .dither_bitmap_tiled(&rect, &dither, &image[1..], &tiling)
+            .is_err());
+    }
+}
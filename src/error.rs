@@ -6,6 +6,19 @@ pub enum Error {
     NotEnoughMemory,
     #[error("failed to open graphics device")]
     FailedToOpenGraphicsDevice,
+    #[error("unknown display driver")]
+    UnknownDisplayDriver,
+    #[error(
+        "no display driver could be started, failed: [{}], not built in: [{}]",
+        .tried.join(", "),
+        .unavailable.join(", ")
+    )]
+    NoDisplayDriver {
+        tried: Vec<String>,
+        unavailable: Vec<String>,
+    },
+    #[error("display left without a driver")]
+    DisplayDriverLost,
     #[error("invalid size")]
     InvalidSize,
     #[error("invalid mask size")]
@@ -22,6 +35,8 @@ pub enum Error {
     MouseCursorUnsupported,
     #[error("invalid dither params")]
     InvalidDitherParams,
+    #[error("invalid dither parameter: {0}")]
+    InvalidDitherParam(&'static str),
     #[error("invalid brightness")]
     InvalidBrightness,
     #[error("invalid gamma")]
@@ -44,12 +59,24 @@ pub enum Error {
     BuiltinFontNotFound,
     #[error("invalid font")]
     InvalidFont,
+    #[error("buffer too small")]
+    BufferTooSmall,
     #[error("request index is out of bounds")]
     OutOfBounds,
     #[error("an IO error occurred")]
     IO(#[from] std::io::Error),
     #[error("invalid FIGfont")]
     InvalidFIGfont,
+    #[error("invalid smush mode")]
+    InvalidSmush,
+    #[error("unknown format")]
+    UnknownFormat,
+    #[error("truncated input")]
+    TruncatedInput,
+    #[error("stream is not seekable")]
+    NotSeekable,
+    #[error("invalid image: {0}")]
+    InvalidImage(String),
     #[error("unknonw error")]
     Unknown(i32),
 }
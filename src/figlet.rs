@@ -0,0 +1,153 @@
+use std::{borrow::Borrow, path::Path, str::FromStr};
+
+use crate::{canvas::Canvas, error::Error, result::Result, Boundaries};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Smush {
+    #[default]
+    Default,
+    Kern,
+    Smush,
+    None,
+    Overlap,
+}
+
+impl ToString for Smush {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Default => "default",
+            Self::Kern => "kern",
+            Self::Smush => "smush",
+            Self::None => "none",
+            Self::Overlap => "overlap",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for Smush {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match raw.to_lowercase().borrow() {
+            "default" => Ok(Self::Default),
+            "kern" => Ok(Self::Kern),
+            "smush" => Ok(Self::Smush),
+            "none" => Ok(Self::None),
+            "overlap" => Ok(Self::Overlap),
+            _ => Err(Error::InvalidSmush),
+        }
+    }
+}
+
+// A canvas dedicated to FIGlet banners. Every render starts from an empty
+// canvas which grows to fit the text.
+pub struct Figlet {
+    canvas: Canvas<'static>,
+}
+
+impl Figlet {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Figlet> {
+        let mut canvas = Self::empty_canvas()?;
+        canvas.set_figfont(path)?;
+
+        Ok(Figlet { canvas })
+    }
+
+    pub fn from_memory<T: AsRef<[u8]>>(data: T) -> Result<Figlet> {
+        let mut canvas = Self::empty_canvas()?;
+        canvas.set_figfont_from_memory(data)?;
+
+        Ok(Figlet { canvas })
+    }
+
+    fn empty_canvas() -> Result<Canvas<'static>> {
+        Canvas::new(&Boundaries {
+            width: 0,
+            height: 0,
+        })
+    }
+
+    pub fn set_width(&mut self, width: usize) {
+        self.canvas.set_figfont_width(width);
+    }
+
+    pub fn set_smush(&mut self, smush: Smush) {
+        self.canvas.set_figfont_smush(smush);
+    }
+
+    pub fn render<S: AsRef<str>>(&mut self, text: S) -> Result<Boundaries> {
+        self.canvas.set_size(&Boundaries {
+            width: 0,
+            height: 0,
+        })?;
+        self.canvas.put_figstr(text)?;
+        self.canvas.flush_figlet()?;
+
+        Ok(self.canvas.size())
+    }
+
+    pub fn size(&self) -> Boundaries {
+        self.canvas.size()
+    }
+
+    pub fn canvas(&self) -> &Canvas<'static> {
+        &self.canvas
+    }
+
+    pub fn into_canvas(self) -> Canvas<'static> {
+        self.canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Figlet, Smush};
+    use crate::{error::Error, Boundaries, Point};
+
+    // One line high font where every glyph is the character itself.
+    fn font() -> String {
+        let mut font = String::from("flf2a$ 1 1 4 -1 0\n");
+
+        for c in (32u8..127).map(char::from) {
+            let end = if c == '@' { '#' } else { '@' };
+            font.push_str(&format!("{}{}{}\n", c, end, end));
+        }
+
+        font
+    }
+
+    #[test]
+    fn smush_names() {
+        assert_eq!("Kern".parse::<Smush>().unwrap(), Smush::Kern);
+        assert_eq!(Smush::default().to_string(), "default");
+        assert!(matches!(
+            "squash".parse::<Smush>(),
+            Err(Error::InvalidSmush)
+        ));
+    }
+
+    #[test]
+    fn render() {
+        let mut figlet = Figlet::from_memory(font()).unwrap();
+        figlet.set_smush(Smush::None);
+
+        let size = figlet.render("hello").unwrap();
+        assert_eq!(
+            size,
+            Boundaries {
+                width: 5,
+                height: 1
+            }
+        );
+        assert_eq!(figlet.canvas().get_char(&Point { x: 1, y: 0 }), 'e' as u32);
+
+        let size = figlet.render("hi").unwrap();
+        assert_eq!(size.width, 2);
+    }
+
+    #[test]
+    fn invalid_font() {
+        assert!(Figlet::from_memory("not a font").is_err());
+    }
+}
@@ -72,6 +72,11 @@ impl Font {
         }
     }
 
+    // Width over height of a glyph, for `Fitting::cell_aspect`.
+    pub fn cell_aspect(&self) -> f32 {
+        self.width() as f32 / self.height().max(1) as f32
+    }
+
     pub fn blocks(&self) -> Vec<u32> {
         let base = unsafe { caca_get_font_blocks(self.as_internal()) };
         let mut last = base;
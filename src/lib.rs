@@ -15,24 +15,33 @@ use libcaca_sys::{caca_get_version, caca_rand};
 
 mod attr;
 pub mod canvas;
+mod color;
+#[cfg(feature = "image")]
+pub mod decode;
 mod display;
 pub mod dither;
 pub mod error;
 pub mod event;
+pub mod figlet;
 mod file;
 mod font;
+pub mod pixels;
 pub mod result;
 mod utils;
+pub mod video;
 
 pub use attr::Argb;
 pub use attr::Attr;
 pub use canvas::Canvas;
-pub use display::Display;
+pub use color::Rgba;
+pub use display::{Display, DisplayBuilder, DisplayDriver};
 pub use dither::Dither;
 pub use event::Event;
 pub use event::EventMask;
+pub use figlet::Figlet;
 pub use file::File;
 pub use font::Font;
+pub use pixels::PixelCanvas;
 
 pub mod prelude {
     pub use crate::error::Error;
@@ -93,6 +102,12 @@ pub struct Triangle {
     pub vertex3: Point,
 }
 
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TexCoord {
+    pub u: f32,
+    pub v: f32,
+}
+
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Color {
@@ -0,0 +1,376 @@
+use std::char;
+
+use crate::{
+    attr::Attr,
+    canvas::Canvas,
+    color::Rgba,
+    dither::{diffusion::resample, Dither},
+    error::Error,
+    result::Result,
+    Point, Rectangle,
+};
+
+// How cells are split into pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SubCell {
+    // ▀ and ▄, 1x2, both pixels keep their own colour
+    HalfBlock,
+    // ▖▘▝▗ and friends, 2x2
+    Quadrant,
+    // Legacy computing sextants, 2x3
+    Sextant,
+    // 2x4 dots
+    Braille,
+}
+
+impl SubCell {
+    // Pixels per cell, horizontally and vertically.
+    pub fn cell_size(&self) -> (usize, usize) {
+        match self {
+            Self::HalfBlock => (1, 2),
+            Self::Quadrant => (2, 2),
+            Self::Sextant => (2, 3),
+            Self::Braille => (2, 4),
+        }
+    }
+
+    // Glyph for a cell's lit pixels, bit `y * width + x` for each one.
+    pub fn glyph(&self, bits: u8) -> char {
+        const QUADRANTS: [char; 16] = [
+            ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+        ];
+        // Braille numbers its dots column by column, with the bottom row
+        // added last
+        const BRAILLE: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+        match self {
+            Self::HalfBlock => [' ', '▀', '▄', '█'][(bits & 0x3) as usize],
+            Self::Quadrant => QUADRANTS[(bits & 0xf) as usize],
+            Self::Sextant => match bits & 0x3f {
+                0 => ' ',
+                0x15 => '▌',
+                0x2a => '▐',
+                0x3f => '█',
+                // The block skips the patterns above
+                bits => {
+                    let skipped = (bits > 0x15) as u32 + (bits > 0x2a) as u32;
+                    char::from_u32(0x1fb00 + bits as u32 - 1 - skipped).unwrap_or('?')
+                }
+            },
+            Self::Braille => {
+                let dots = (0..8)
+                    .filter(|bit| bits & (1 << bit) != 0)
+                    .fold(0, |dots, bit| dots | BRAILLE[bit]);
+                char::from_u32(0x2800 + dots as u32).unwrap_or('?')
+            }
+        }
+    }
+}
+
+// A grid of sub-cell pixels, lit ones have a colour, every cell has a
+// background colour.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelCanvas {
+    mode: SubCell,
+    columns: usize,
+    rows: usize,
+    pixels: Vec<Option<Rgba>>,
+    backgrounds: Vec<Rgba>,
+}
+
+impl PixelCanvas {
+    // Sized in cells, see `width` and `height` for the pixels.
+    pub fn new(mode: SubCell, columns: usize, rows: usize) -> PixelCanvas {
+        let (cw, ch) = mode.cell_size();
+
+        PixelCanvas {
+            mode,
+            columns,
+            rows,
+            pixels: vec![None; columns * cw * rows * ch],
+            backgrounds: vec![Rgba::default(); columns * rows],
+        }
+    }
+
+    // Each cell is split in two groups around its mean luminance, the
+    // bright pixels are lit with their average colour over the average of
+    // the dark ones.
+    pub fn from_bitmap(
+        mode: SubCell,
+        columns: usize,
+        rows: usize,
+        dither: &Dither,
+        image: &[u8],
+    ) -> Result<PixelCanvas> {
+        if image.len() < dither.buffer_len() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let mut canvas = Self::new(mode, columns, rows);
+        let (width, height) = (canvas.width(), canvas.height());
+        if width == 0 || height == 0 {
+            return Ok(canvas);
+        }
+
+        let samples = resample(dither, image, width, height);
+        let color = |sample: &[f32; 4]| {
+            let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            Rgba::new(
+                channel(sample[0]),
+                channel(sample[1]),
+                channel(sample[2]),
+                channel(sample[3]),
+            )
+        };
+
+        if mode == SubCell::HalfBlock {
+            for (pixel, sample) in canvas.pixels.iter_mut().zip(&samples) {
+                *pixel = Some(color(sample));
+            }
+            return Ok(canvas);
+        }
+
+        let (cw, ch) = mode.cell_size();
+        for row in 0..rows {
+            for column in 0..columns {
+                let indices: Vec<usize> = (0..ch)
+                    .flat_map(|y| (0..cw).map(move |x| (row * ch + y) * width + column * cw + x))
+                    .collect();
+                let lumas: Vec<u8> = indices.iter().map(|&i| color(&samples[i]).luma()).collect();
+                let mean = lumas.iter().map(|&l| l as u32).sum::<u32>() / lumas.len() as u32;
+
+                let average = |bright: bool| {
+                    let group: Vec<&[f32; 4]> = indices
+                        .iter()
+                        .zip(&lumas)
+                        .filter(|&(_, &l)| (l as u32 > mean) == bright)
+                        .map(|(&i, _)| &samples[i])
+                        .collect();
+                    if group.is_empty() {
+                        return None;
+                    }
+
+                    let mut sum = [0f32; 4];
+                    for sample in &group {
+                        for (total, channel) in sum.iter_mut().zip(sample.iter()) {
+                            *total += channel / group.len() as f32;
+                        }
+                    }
+                    Some(color(&sum))
+                };
+
+                let (bright, dark) = (average(true), average(false));
+                canvas.backgrounds[row * columns + column] = dark.unwrap_or_default();
+                for (&i, &l) in indices.iter().zip(&lumas) {
+                    if l as u32 > mean {
+                        canvas.pixels[i] = bright;
+                    }
+                }
+            }
+        }
+
+        Ok(canvas)
+    }
+
+    pub fn mode(&self) -> SubCell {
+        self.mode
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn width(&self) -> usize {
+        self.columns * self.mode.cell_size().0
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows * self.mode.cell_size().1
+    }
+
+    fn index(&self, x: usize, y: usize) -> Result<usize> {
+        if x >= self.width() || y >= self.height() {
+            Err(Error::OutOfBounds)
+        } else {
+            Ok(y * self.width() + x)
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Result<Option<Rgba>> {
+        Ok(self.pixels[self.index(x, y)?])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Rgba) -> Result<()> {
+        let index = self.index(x, y)?;
+        self.pixels[index] = Some(color);
+        Ok(())
+    }
+
+    pub fn unset(&mut self, x: usize, y: usize) -> Result<()> {
+        let index = self.index(x, y)?;
+        self.pixels[index] = None;
+        Ok(())
+    }
+
+    // Recolours the lit pixels of a cell.
+    pub fn set_cell_color(&mut self, column: usize, row: usize, color: Rgba) -> Result<()> {
+        let (cw, ch) = self.mode.cell_size();
+        for y in 0..ch {
+            for x in 0..cw {
+                let index = self.index(column * cw + x, row * ch + y)?;
+                if self.pixels[index].is_some() {
+                    self.pixels[index] = Some(color);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_cell_background(&mut self, column: usize, row: usize, color: Rgba) -> Result<()> {
+        if column >= self.columns || row >= self.rows {
+            return Err(Error::OutOfBounds);
+        }
+
+        self.backgrounds[row * self.columns + column] = color;
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel = None);
+    }
+
+    // Glyph, foreground and background of a cell.
+    pub fn cell(&self, column: usize, row: usize) -> Result<(char, Rgba, Rgba)> {
+        if column >= self.columns || row >= self.rows {
+            return Err(Error::OutOfBounds);
+        }
+
+        let (cw, ch) = self.mode.cell_size();
+        let background = self.backgrounds[row * self.columns + column];
+        let pixels: Vec<Option<Rgba>> = (0..ch)
+            .flat_map(|y| (0..cw).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixels[(row * ch + y) * self.width() + column * cw + x])
+            .collect();
+
+        if self.mode == SubCell::HalfBlock {
+            return Ok(match (pixels[0], pixels[1]) {
+                (Some(top), Some(bottom)) => ('▀', top, bottom),
+                (Some(top), None) => ('▀', top, background),
+                (None, Some(bottom)) => ('▄', bottom, background),
+                (None, None) => (' ', background, background),
+            });
+        }
+
+        let bits = pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, pixel)| pixel.is_some())
+            .fold(0u8, |bits, (i, _)| bits | 1 << i);
+        let lit: Vec<Rgba> = pixels.iter().filter_map(|&pixel| pixel).collect();
+        let foreground = if lit.is_empty() {
+            background
+        } else {
+            let average = |channel: fn(&Rgba) -> u8| {
+                (lit.iter().map(|c| channel(c) as usize).sum::<usize>() / lit.len()) as u8
+            };
+            Rgba::new(
+                average(|c| c.r),
+                average(|c| c.g),
+                average(|c| c.b),
+                average(|c| c.a),
+            )
+        };
+
+        Ok((self.mode.glyph(bits), foreground, background))
+    }
+
+    // Writes every cell with `point` as the top left corner, colours go
+    // through libcaca's 12 bits ARGB.
+    pub fn draw(&self, canvas: &mut Canvas, point: &Point) -> Result<()> {
+        // (-1, -1) reads the current attribute
+        let previous = canvas.get_attr(&Point { x: -1, y: -1 });
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let (glyph, fg, bg) = self.cell(column, row)?;
+                canvas.set_attr(Attr::argb(fg, bg));
+                canvas.put_char(
+                    &Point {
+                        x: point.x + column as i32,
+                        y: point.y + row as i32,
+                    },
+                    glyph as u32,
+                );
+            }
+        }
+
+        canvas.set_attr(previous);
+        Ok(())
+    }
+}
+
+pub(crate) fn dither_bitmap(
+    canvas: &mut Canvas,
+    rect: &Rectangle,
+    dither: &Dither,
+    image: &[u8],
+    mode: SubCell,
+) -> Result<()> {
+    PixelCanvas::from_bitmap(mode, rect.width, rect.height, dither, image)?.draw(
+        canvas,
+        &Point {
+            x: rect.x,
+            y: rect.y,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PixelCanvas, SubCell};
+    use crate::{Boundaries, Canvas, Point, Rgba};
+
+    #[test]
+    fn glyphs() {
+        assert_eq!(SubCell::Quadrant.glyph(0b1001), '▚');
+        assert_eq!(SubCell::Sextant.glyph(0b000001), '\u{1fb00}');
+        assert_eq!(SubCell::Sextant.glyph(0b010101), '▌');
+        assert_eq!(SubCell::Sextant.glyph(0b010110), '\u{1fb14}');
+        assert_eq!(SubCell::Sextant.glyph(0b111110), '\u{1fb3b}');
+        // Left column, then the bottom right dot
+        assert_eq!(SubCell::Braille.glyph(0b01010101), '\u{2847}');
+        assert_eq!(SubCell::Braille.glyph(0b10000000), '\u{2880}');
+    }
+
+    #[test]
+    fn cells() {
+        let red = Rgba::rgb(0xff, 0, 0);
+        let blue = Rgba::rgb(0, 0, 0xff);
+
+        let mut pixels = PixelCanvas::new(SubCell::HalfBlock, 2, 1);
+        pixels.set(0, 1, red).unwrap();
+        assert_eq!(pixels.cell(0, 0).unwrap(), ('▄', red, Rgba::default()));
+        assert!(pixels.set(0, 2, red).is_err());
+
+        let mut pixels = PixelCanvas::new(SubCell::Braille, 1, 1);
+        pixels.set(0, 0, red).unwrap();
+        pixels.set(1, 3, blue).unwrap();
+        pixels.set_cell_background(0, 0, blue).unwrap();
+        assert_eq!(
+            pixels.cell(0, 0).unwrap(),
+            ('\u{2881}', Rgba::rgb(0x7f, 0, 0x7f), blue)
+        );
+
+        let mut canvas = Canvas::new(&Boundaries {
+            width: 2,
+            height: 1,
+        })
+        .unwrap();
+        pixels.draw(&mut canvas, &Point { x: 1, y: 0 }).unwrap();
+        assert_eq!(canvas.chars()[1], 0x2881);
+    }
+}
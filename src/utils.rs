@@ -1,4 +1,8 @@
-use std::ffi::CString;
+use std::{
+    borrow::Cow,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+};
 
 pub fn lossy_cstring<S: AsRef<str>>(string: S) -> CString {
     match CString::new(string.as_ref()) {
@@ -6,3 +10,22 @@ pub fn lossy_cstring<S: AsRef<str>>(string: S) -> CString {
         Err(_) => CString::new(string.as_ref().replace('\0', "")).expect("string has no nulls"),
     }
 }
+
+// libcaca returns its lists as NULL-terminated arrays of alternated
+// name/description strings.
+pub unsafe fn pairs_list(
+    mut list: *const *const c_char,
+) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+    let mut res = Vec::new();
+
+    while !(*list).is_null() && !(*list.add(1)).is_null() {
+        let name = CStr::from_ptr(*list).to_string_lossy();
+        let description = CStr::from_ptr(*list.add(1)).to_string_lossy();
+
+        res.push((name, description));
+
+        list = list.add(2);
+    }
+
+    res
+}
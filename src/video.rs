@@ -0,0 +1,742 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    dither::{Dither, Fitting, PixelFormat},
+    error::Error,
+    event::{Event, EventMask, Key},
+    result::Result,
+    Display, Rectangle,
+};
+
+// A sequence of same sized frames.
+pub trait FrameSource {
+    fn format(&self) -> PixelFormat;
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    // Frames per second
+    fn frame_rate(&self) -> f64;
+    // Index of the frame `read_frame` returns next
+    fn position(&self) -> usize;
+    // Fills `buffer` with the next frame, false at the end of the stream.
+    fn read_frame(&mut self, buffer: &mut Vec<u8>) -> Result<bool>;
+    // Moves past the next frame without converting it.
+    fn skip_frame(&mut self) -> Result<bool>;
+    // Going forward always works, going back needs a seekable reader.
+    fn seek(&mut self, frame: usize) -> Result<()>;
+}
+
+type Rewind<R> = fn(&mut BufReader<R>, u64) -> io::Result<()>;
+
+// Fixed size records after a header, what both readers boil down to.
+struct Records<R> {
+    reader: BufReader<R>,
+    rewind: Option<Rewind<R>>,
+    start: u64,
+    len: usize,
+    position: usize,
+}
+
+impl<R: Read> Records<R> {
+    fn skip(&mut self, len: usize) -> Result<bool> {
+        let skipped = io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+        match skipped {
+            0 if len > 0 => Ok(false),
+            n if n < len as u64 => Err(Error::TruncatedInput),
+            _ => Ok(true),
+        }
+    }
+
+    fn seek(&mut self, frame: usize, skip: impl Fn(&mut Self) -> Result<bool>) -> Result<()> {
+        if frame < self.position {
+            let rewind = self.rewind.ok_or(Error::NotSeekable)?;
+            rewind(&mut self.reader, self.start + (frame * self.len) as u64)?;
+            self.position = frame;
+        }
+
+        while self.position < frame && skip(self)? {}
+
+        Ok(())
+    }
+}
+
+// Reads until `buffer` is full, false if the stream ended right away.
+fn fill<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<bool> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    match read {
+        0 if !buffer.is_empty() => Ok(false),
+        n if n < buffer.len() => Err(Error::TruncatedInput),
+        _ => Ok(true),
+    }
+}
+
+fn rewind<R: Seek>(reader: &mut BufReader<R>, offset: u64) -> io::Result<()> {
+    reader.seek(SeekFrom::Start(offset)).map(drop)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Chroma {
+    C420,
+    C422,
+    C444,
+    Mono,
+}
+
+impl Chroma {
+    // Size of one chroma plane.
+    fn plane(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Self::C420 => (width.div_ceil(2), height.div_ceil(2)),
+            Self::C422 => (width.div_ceil(2), height),
+            Self::C444 => (width, height),
+            Self::Mono => (0, 0),
+        }
+    }
+}
+
+// A YUV4MPEG2 stream, frames come out as RGB24. Only 8 bits 4:2:0, 4:2:2,
+// 4:4:4 and mono streams are handled, seeking back expects frame headers
+// without parameters, which is what encoders write.
+pub struct Y4mReader<R> {
+    records: Records<R>,
+    width: usize,
+    height: usize,
+    frame_rate: f64,
+    chroma: Chroma,
+    planes: Vec<u8>,
+}
+
+impl Y4mReader<fs::File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::seekable(fs::File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> Y4mReader<R> {
+    pub fn seekable(mut reader: R) -> Result<Self> {
+        let offset = reader.stream_position()?;
+        let mut y4m = Self::new(reader)?;
+        y4m.records.start += offset;
+        y4m.records.rewind = Some(rewind::<R>);
+        Ok(y4m)
+    }
+}
+
+impl<R: Read> Y4mReader<R> {
+    // Pipes and anything else that can only go forward.
+    pub fn new(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut header = Vec::new();
+        reader.read_until(b'\n', &mut header)?;
+
+        let header = header
+            .strip_suffix(b"\n")
+            .and_then(|header| std::str::from_utf8(header).ok())
+            .and_then(|header| header.strip_prefix("YUV4MPEG2 "))
+            .ok_or(Error::UnknownFormat)?;
+
+        let (mut width, mut height) = (None, None);
+        let mut frame_rate = 25.0;
+        let mut chroma = Chroma::C420;
+
+        for token in header.split(' ').filter(|token| !token.is_empty()) {
+            let (tag, value) = token.split_at(1);
+            match tag {
+                "W" => width = value.parse::<usize>().ok(),
+                "H" => height = value.parse::<usize>().ok(),
+                "F" => {
+                    let mut ratio = value.splitn(2, ':').map(str::parse::<u32>);
+                    frame_rate = match (ratio.next(), ratio.next()) {
+                        (Some(Ok(n)), Some(Ok(d))) if n > 0 && d > 0 => n as f64 / d as f64,
+                        _ => return Err(Error::UnknownFormat),
+                    };
+                }
+                "C" => {
+                    chroma = match value {
+                        "420" | "420jpeg" | "420paldv" | "420mpeg2" => Chroma::C420,
+                        "422" => Chroma::C422,
+                        "444" => Chroma::C444,
+                        "mono" => Chroma::Mono,
+                        _ => return Err(Error::UnknownFormat),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let (width, height) = match (width, height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => (width, height),
+            _ => return Err(Error::InvalidSize),
+        };
+        let (cw, ch) = chroma.plane(width, height);
+        let planes = width * height + 2 * cw * ch;
+
+        Ok(Y4mReader {
+            records: Records {
+                reader,
+                rewind: None,
+                start: header.len() as u64 + 11,
+                len: FRAME.len() + planes,
+                position: 0,
+            },
+            width,
+            height,
+            frame_rate,
+            chroma,
+            planes: vec![0; planes],
+        })
+    }
+
+    // Consumes the "FRAME" line, false at the end of the stream.
+    fn frame_header(records: &mut Records<R>) -> Result<bool> {
+        let mut line = Vec::with_capacity(FRAME.len());
+        if records.reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(false);
+        }
+
+        if line.starts_with(&FRAME[..5]) && line.ends_with(b"\n") {
+            Ok(true)
+        } else {
+            Err(Error::TruncatedInput)
+        }
+    }
+
+    fn skip(records: &mut Records<R>) -> Result<bool> {
+        if !Self::frame_header(records)? {
+            return Ok(false);
+        }
+
+        if !records.skip(records.len - FRAME.len())? {
+            return Err(Error::TruncatedInput);
+        }
+        records.position += 1;
+
+        Ok(true)
+    }
+}
+
+const FRAME: &[u8] = b"FRAME\n";
+
+impl<R: Read> FrameSource for Y4mReader<R> {
+    fn format(&self) -> PixelFormat {
+        PixelFormat::Rgb24
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn frame_rate(&self) -> f64 {
+        self.frame_rate
+    }
+
+    fn position(&self) -> usize {
+        self.records.position
+    }
+
+    fn read_frame(&mut self, buffer: &mut Vec<u8>) -> Result<bool> {
+        if !Self::frame_header(&mut self.records)? {
+            return Ok(false);
+        }
+
+        if !fill(&mut self.records.reader, &mut self.planes)? {
+            return Err(Error::TruncatedInput);
+        }
+        self.records.position += 1;
+
+        yuv_to_rgb(self.chroma, &self.planes, self.width, self.height, buffer);
+        Ok(true)
+    }
+
+    fn skip_frame(&mut self) -> Result<bool> {
+        Self::skip(&mut self.records)
+    }
+
+    fn seek(&mut self, frame: usize) -> Result<()> {
+        self.records.seek(frame, Self::skip)
+    }
+}
+
+// BT.601 studio range, the Y4M default.
+fn yuv_to_rgb(chroma: Chroma, planes: &[u8], width: usize, height: usize, out: &mut Vec<u8>) {
+    let (cw, ch) = chroma.plane(width, height);
+    let (luma, rest) = planes.split_at(width * height);
+    let (u, v) = rest.split_at(cw * ch);
+
+    out.clear();
+    out.reserve(width * height * 3);
+
+    for y in 0..height {
+        for x in 0..width {
+            let c = 298 * (luma[y * width + x] as i32 - 16);
+            let (d, e) = if chroma == Chroma::Mono {
+                (0, 0)
+            } else {
+                let at = (y * ch / height) * cw + x * cw / width;
+                (u[at] as i32 - 128, v[at] as i32 - 128)
+            };
+
+            let clamp = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+            out.extend_from_slice(&[
+                clamp(c + 409 * e),
+                clamp(c - 100 * d - 208 * e),
+                clamp(c + 516 * d),
+            ]);
+        }
+    }
+}
+
+// Headerless frames in any pixel format the dither understands, back to
+// back, with tightly packed rows.
+pub struct RawReader<R> {
+    records: Records<R>,
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+    frame_rate: f64,
+}
+
+impl RawReader<fs::File> {
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        format: PixelFormat,
+        width: usize,
+        height: usize,
+        frame_rate: f64,
+    ) -> Result<Self> {
+        Self::seekable(fs::File::open(path)?, format, width, height, frame_rate)
+    }
+}
+
+impl<R: Read + Seek> RawReader<R> {
+    pub fn seekable(
+        mut reader: R,
+        format: PixelFormat,
+        width: usize,
+        height: usize,
+        frame_rate: f64,
+    ) -> Result<Self> {
+        let offset = reader.stream_position()?;
+        let mut raw = Self::new(reader, format, width, height, frame_rate)?;
+        raw.records.start = offset;
+        raw.records.rewind = Some(rewind::<R>);
+        Ok(raw)
+    }
+}
+
+impl<R: Read> RawReader<R> {
+    pub fn new(
+        reader: R,
+        format: PixelFormat,
+        width: usize,
+        height: usize,
+        frame_rate: f64,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidSize);
+        }
+        if !(frame_rate > 0.0 && frame_rate.is_finite()) {
+            return Err(Error::InvalidRefreshDelay);
+        }
+
+        Ok(RawReader {
+            records: Records {
+                reader: BufReader::new(reader),
+                rewind: None,
+                start: 0,
+                len: width * height * format.bytes_per_pixel(),
+                position: 0,
+            },
+            format,
+            width,
+            height,
+            frame_rate,
+        })
+    }
+
+    fn skip(records: &mut Records<R>) -> Result<bool> {
+        let skipped = records.skip(records.len)?;
+        records.position += skipped as usize;
+        Ok(skipped)
+    }
+}
+
+impl<R: Read> FrameSource for RawReader<R> {
+    fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn frame_rate(&self) -> f64 {
+        self.frame_rate
+    }
+
+    fn position(&self) -> usize {
+        self.records.position
+    }
+
+    fn read_frame(&mut self, buffer: &mut Vec<u8>) -> Result<bool> {
+        buffer.resize(self.records.len, 0);
+        let read = fill(&mut self.records.reader, buffer)?;
+        self.records.position += read as usize;
+        Ok(read)
+    }
+
+    fn skip_frame(&mut self) -> Result<bool> {
+        Self::skip(&mut self.records)
+    }
+
+    fn seek(&mut self, frame: usize) -> Result<()> {
+        self.records.seek(frame, Self::skip)
+    }
+}
+
+// What a key press asks the player to do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Control {
+    Pause,
+    // Seconds, negative goes back
+    Seek(i32),
+    // Frames, negative goes back, pauses playback
+    Step(i32),
+    Quit,
+}
+
+impl Control {
+    // Space pauses, left and right arrows seek 5 seconds, page up and down
+    // a minute, '.' and ',' step a frame, 'q' and escape stop.
+    pub fn from_event(event: &Event) -> Option<Control> {
+        match event {
+            Event::Quit => Some(Self::Quit),
+            Event::KeyPress(key, _, _) => match key {
+                Key::Char(' ') | Key::Pause => Some(Self::Pause),
+                Key::Right => Some(Self::Seek(5)),
+                Key::Left => Some(Self::Seek(-5)),
+                Key::PageDown => Some(Self::Seek(60)),
+                Key::PageUp => Some(Self::Seek(-60)),
+                Key::Char('.') => Some(Self::Step(1)),
+                Key::Char(',') => Some(Self::Step(-1)),
+                Key::Char('q') | Key::Escape => Some(Self::Quit),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// How a playback went.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Playback {
+    pub shown: usize,
+    pub dropped: usize,
+}
+
+// Plays a frame source on a display, dithering every frame with the same
+// `Dither`, see `Control` for the keys.
+pub struct Player<S> {
+    source: S,
+    dither: Dither,
+    frame: Vec<u8>,
+    fitting: Option<Fitting>,
+    paused: bool,
+}
+
+impl<S: FrameSource> Player<S> {
+    pub fn new(source: S) -> Result<Self> {
+        let dither = Dither::builder(source.format(), source.width(), source.height()).build()?;
+
+        Ok(Player {
+            source,
+            dither,
+            frame: Vec::new(),
+            fitting: None,
+            paused: false,
+        })
+    }
+
+    // Tweak it to change how frames are dithered.
+    pub fn dither(&self) -> &Dither {
+        &self.dither
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    // Starts paused, on the first frame.
+    pub fn pause(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    // Defaults to fitting the whole display, with its cell aspect ratio.
+    pub fn fitting(mut self, fitting: Fitting) -> Self {
+        self.fitting = Some(fitting);
+        self
+    }
+
+    // Plays until the end of the stream or until asked to quit. Frames are
+    // paced with the display's refresh delay, those late by more than a
+    // frame are dropped.
+    pub fn play(&mut self, display: &mut Display) -> Result<Playback> {
+        let rate = self.source.frame_rate();
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(Error::InvalidRefreshDelay);
+        }
+        display.set_time(Duration::from_secs_f64(1.0 / rate))?;
+
+        let fitting = self.fitting.unwrap_or_else(|| {
+            let aspect = display.cell_aspect();
+            if aspect.is_normal() {
+                Fitting::default().cell_aspect(aspect)
+            } else {
+                Fitting::default()
+            }
+        });
+
+        let mut playback = Playback::default();
+        let (mut clock, mut origin) = (Instant::now(), self.source.position());
+        // The first frame shows up even when starting paused
+        let mut show = true;
+
+        loop {
+            let wait = if self.paused && !show {
+                IDLE
+            } else {
+                Duration::default()
+            };
+            let mut event = display.poll_event(EventMask::KEY_PRESS | EventMask::QUIT, wait);
+
+            while let Some(control) = event.as_ref().and_then(Control::from_event) {
+                match control {
+                    Control::Quit => return Ok(playback),
+                    control => show |= self.control(control, rate)?,
+                }
+
+                clock = Instant::now();
+                origin = self.source.position();
+                event =
+                    display.poll_event(EventMask::KEY_PRESS | EventMask::QUIT, Duration::default());
+            }
+
+            if self.paused && !show {
+                continue;
+            }
+            show = false;
+
+            if !self.paused {
+                let due = origin + (clock.elapsed().as_secs_f64() * rate) as usize;
+                while self.source.position() < due {
+                    if !self.source.skip_frame()? {
+                        return Ok(playback);
+                    }
+                    playback.dropped += 1;
+                }
+            }
+
+            if !self.source.read_frame(&mut self.frame)? {
+                return Ok(playback);
+            }
+
+            {
+                let mut canvas = display.canvas();
+                let area = Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: canvas.width(),
+                    height: canvas.height(),
+                };
+                canvas.dither_bitmap_fit(&area, &self.dither, &self.frame, &fitting)?;
+            }
+            display.refresh();
+            playback.shown += 1;
+        }
+    }
+
+    // True when the frame at the new position has to be shown.
+    fn control(&mut self, control: Control, rate: f64) -> Result<bool> {
+        match control {
+            Control::Quit => Ok(false),
+            Control::Pause => {
+                self.paused = !self.paused;
+                Ok(false)
+            }
+            Control::Seek(seconds) => {
+                let offset = (seconds as f64 * rate).round() as i64;
+                // The frame on screen is the one before `position`
+                self.seek_by(offset - 1)
+            }
+            Control::Step(frames) => {
+                self.paused = true;
+                self.seek_by(frames as i64 - 1)
+            }
+        }
+    }
+
+    // Streams that can't go back stay where they are, false then.
+    fn seek_by(&mut self, offset: i64) -> Result<bool> {
+        let target = (self.source.position() as i64 + offset).max(0) as usize;
+        match self.source.seek(target) {
+            Ok(()) => Ok(true),
+            Err(Error::NotSeekable) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// How long a paused player waits for key presses at a time.
+const IDLE: Duration = Duration::from_millis(50);
+
+#[cfg(test)]
+mod tests {
+    use super::{Control, FrameSource, Player, RawReader, Y4mReader};
+    use crate::{
+        dither::PixelFormat,
+        error::Error,
+        event::{Event, Key},
+        Display,
+    };
+    use std::io::Cursor;
+
+    // 4x2 4:2:0 frames, one luma level each, neutral chroma
+    fn y4m(levels: &[u8]) -> Vec<u8> {
+        let mut data = b"YUV4MPEG2 W4 H2 F1000:1 Ip A1:1 C420jpeg\n".to_vec();
+        for &level in levels {
+            data.extend_from_slice(b"FRAME\n");
+            data.extend_from_slice(&[level; 8]);
+            data.extend_from_slice(&[128; 4]);
+        }
+        data
+    }
+
+    #[test]
+    fn y4m_frames() {
+        let mut reader = Y4mReader::seekable(Cursor::new(y4m(&[16, 235, 126]))).unwrap();
+        assert_eq!((reader.width(), reader.height()), (4, 2));
+        assert_eq!(reader.frame_rate(), 1000.0);
+        assert_eq!(reader.format(), PixelFormat::Rgb24);
+
+        let mut frame = Vec::new();
+        assert!(reader.read_frame(&mut frame).unwrap());
+        assert_eq!(frame, vec![0; 24]);
+        assert!(reader.skip_frame().unwrap());
+        assert!(reader.read_frame(&mut frame).unwrap());
+        assert_eq!(&frame[..3], &[128, 128, 128]);
+        assert!(!reader.read_frame(&mut frame).unwrap());
+
+        reader.seek(1).unwrap();
+        assert_eq!(reader.position(), 1);
+        assert!(reader.read_frame(&mut frame).unwrap());
+        assert_eq!(frame, vec![255; 24]);
+
+        // Pipes can only go forward
+        let mut pipe = Y4mReader::new(Cursor::new(y4m(&[16, 235]))).unwrap();
+        pipe.seek(1).unwrap();
+        assert!(matches!(pipe.seek(0), Err(Error::NotSeekable)));
+
+        let mut truncated = y4m(&[16]);
+        truncated.pop();
+        let mut reader = Y4mReader::new(&truncated[..]).unwrap();
+        assert!(matches!(
+            reader.read_frame(&mut frame),
+            Err(Error::TruncatedInput)
+        ));
+
+        assert!(Y4mReader::new(&b"P6\n"[..]).is_err());
+        assert!(Y4mReader::new(&b"YUV4MPEG2 W4 H2 C420p10\n"[..]).is_err());
+    }
+
+    #[test]
+    fn raw_frames() {
+        let data: Vec<u8> = (0..4u8).flat_map(|frame| vec![frame; 2 * 2 * 3]).collect();
+        let mut reader =
+            RawReader::seekable(Cursor::new(data), PixelFormat::Rgb24, 2, 2, 30.0).unwrap();
+
+        let mut frame = Vec::new();
+        reader.seek(2).unwrap();
+        assert!(reader.read_frame(&mut frame).unwrap());
+        assert_eq!(frame, vec![2; 12]);
+        reader.seek(0).unwrap();
+        assert!(reader.read_frame(&mut frame).unwrap());
+        assert_eq!(frame, vec![0; 12]);
+
+        assert!(RawReader::new(&[][..], PixelFormat::Gray8, 0, 2, 30.0).is_err());
+        assert!(RawReader::new(&[][..], PixelFormat::Gray8, 2, 2, 0.0).is_err());
+    }
+
+    #[test]
+    fn controls() {
+        let key = |key| Event::KeyPress(key, 0, None);
+
+        assert_eq!(
+            Control::from_event(&key(Key::Char(' '))),
+            Some(Control::Pause)
+        );
+        assert_eq!(
+            Control::from_event(&key(Key::Left)),
+            Some(Control::Seek(-5))
+        );
+        assert_eq!(
+            Control::from_event(&key(Key::Char('.'))),
+            Some(Control::Step(1))
+        );
+        assert_eq!(Control::from_event(&Event::Quit), Some(Control::Quit));
+        assert_eq!(Control::from_event(&key(Key::Char('x'))), None);
+    }
+
+    #[test]
+    fn play() {
+        let mut display = Display::new_with_driver(None, "null").unwrap();
+        let mut player =
+            Player::new(Y4mReader::new(Cursor::new(y4m(&[16, 235, 126]))).unwrap()).unwrap();
+
+        let playback = player.play(&mut display).unwrap();
+        assert_eq!(playback.shown + playback.dropped, 3);
+        assert_eq!(player.source().position(), 3);
+    }
+
+    #[test]
+    fn step_back_on_pipe() {
+        let mut player =
+            Player::new(Y4mReader::new(Cursor::new(y4m(&[16, 235, 126]))).unwrap()).unwrap();
+        let mut frame = Vec::new();
+        assert!(player.source.read_frame(&mut frame).unwrap());
+
+        // Refused, the frame on screen stays
+        assert!(!player.control(Control::Step(-1), 1000.0).unwrap());
+        assert!(!player.control(Control::Seek(-5), 1000.0).unwrap());
+        assert!(player.paused());
+        assert_eq!(player.source().position(), 1);
+
+        assert!(player.control(Control::Step(2), 1000.0).unwrap());
+        assert_eq!(player.source().position(), 2);
+    }
+}